@@ -0,0 +1,135 @@
+use bevy::prelude::*;
+
+use crate::field::GridGeometry;
+use crate::heat_diffusion::ThermalBoundaries;
+use crate::organism::{self, Energy, Organism, OrganismSet, Velocity};
+
+pub struct BoundaryPlugin {
+    pub mode: BoundaryMode,
+}
+
+/// What happens to organisms that move past the edge of the grid
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoundaryMode {
+    /// Re-enter from the opposite edge, making the world a torus
+    Wrap,
+    /// Reflect off the edge
+    Bounce,
+    /// Die on leaving the world
+    Absorb,
+}
+
+impl BoundaryMode {
+    /// Axes along which organisms re-enter from the opposite edge
+    pub fn wrap(self) -> BVec2 {
+        BVec2::splat(self == BoundaryMode::Wrap)
+    }
+
+    fn next(self) -> Self {
+        match self {
+            BoundaryMode::Wrap => BoundaryMode::Bounce,
+            BoundaryMode::Bounce => BoundaryMode::Absorb,
+            BoundaryMode::Absorb => BoundaryMode::Wrap,
+        }
+    }
+}
+
+/// Axes along which the world wraps around, none if organisms don't move
+/// within any boundary
+pub fn world_wrap(mode: Option<&BoundaryMode>) -> BVec2 {
+    mode.map_or(BVec2::FALSE, |mode| mode.wrap())
+}
+
+/// Checks that organisms wrap around exactly the edges across which heat flows
+/// to the opposite side, so that the world is a torus for both or for neither
+pub fn check_wrap_matches(mode: BoundaryMode, boundaries: &ThermalBoundaries) {
+    assert_eq!(
+        mode.wrap(),
+        boundaries.wrap(),
+        "boundary mode {mode:?} must wrap along the same axes as the periodic thermal boundaries"
+    );
+}
+
+impl Plugin for BoundaryPlugin {
+    fn build(&self, app: &mut App) {
+        if let Some(boundaries) = app.world().get_resource::<ThermalBoundaries>() {
+            check_wrap_matches(self.mode, boundaries);
+        }
+
+        app.insert_resource(self.mode)
+            .add_systems(
+                FixedUpdate,
+                apply_boundary
+                    .in_set(OrganismSet::Move)
+                    .after(organism::apply_velocity),
+            )
+            .add_systems(Update, set_boundary_mode);
+    }
+}
+
+fn apply_boundary(
+    mut query: Query<(&mut Transform, &mut Velocity, &mut Energy), With<Organism>>,
+    mode: Res<BoundaryMode>,
+    geometry: Res<GridGeometry>,
+) {
+    let bounds = geometry.bounds();
+
+    for (mut transform, mut velocity, mut energy) in query.iter_mut() {
+        let position = transform.translation.truncate();
+        if bounds.contains(position) {
+            continue;
+        }
+
+        match *mode {
+            BoundaryMode::Wrap => {
+                let wrapped = geometry.wrap_position(position, mode.wrap());
+                transform.translation.x = wrapped.x;
+                transform.translation.y = wrapped.y;
+            }
+            BoundaryMode::Bounce => {
+                if position.x < bounds.min.x || position.x > bounds.max.x {
+                    transform.translation.x = reflect(position.x, bounds.min.x, bounds.max.x);
+                    velocity.x = -velocity.x;
+                }
+                if position.y < bounds.min.y || position.y > bounds.max.y {
+                    transform.translation.y = reflect(position.y, bounds.min.y, bounds.max.y);
+                    velocity.y = -velocity.y;
+                }
+            }
+            BoundaryMode::Absorb => {
+                // Organisms without energy are despawned at the end of the tick
+                energy.0 = 0.0;
+            }
+        }
+    }
+}
+
+fn set_boundary_mode(
+    mut mode: ResMut<BoundaryMode>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    thermal_boundaries: Option<Res<ThermalBoundaries>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyB) {
+        return;
+    }
+
+    let mut next = mode.next();
+    // Skip the modes that would disagree with the heat grid about which edges wrap
+    if let Some(boundaries) = thermal_boundaries {
+        while next.wrap() != boundaries.wrap() && next != *mode {
+            next = next.next();
+        }
+    }
+
+    *mode = next;
+    info!("boundary mode: {:?}", *mode);
+}
+
+/// Mirrors a coordinate that overshot `min` or `max` back into the range
+fn reflect(value: f32, min: f32, max: f32) -> f32 {
+    if value < min {
+        (2.0 * min - value).min(max)
+    } else {
+        (2.0 * max - value).max(min)
+    }
+}
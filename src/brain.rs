@@ -0,0 +1,127 @@
+use bevy::prelude::*;
+
+use crate::boundary::{self, BoundaryMode};
+use crate::genome::{Genome, TrophicLevel};
+use crate::heat_diffusion::TemperatureField;
+use crate::mating::{self, MatingConfig};
+use crate::organism::{Energy, Organism, OrganismSet, Velocity};
+use crate::predation::PredationConfig;
+use crate::spatial::SpatialIndex;
+use crate::steering::SteeringForce;
+use crate::vegetation::BiomassField;
+
+const TEMPERATURE_SCALE: f32 = 50.0; // Deviation from the preferred temperature sensed as fully hot or cold
+
+/// Lets organisms with a neural network in their genome decide where to go and
+/// whether to eat and reproduce, instead of following the scripted steering
+/// behaviors
+pub struct BrainPlugin;
+
+impl Plugin for BrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(FixedUpdate, think.in_set(OrganismSet::Steer));
+    }
+}
+
+/// Decisions made this tick by the network in an organism's genome
+#[derive(Component, Default)]
+pub struct Brain {
+    pub wants_to_eat: bool,
+    pub wants_to_reproduce: bool,
+}
+
+/// Whether an organism is willing to eat. Organisms without a brain always are.
+pub fn wants_to_eat(brain: Option<&Brain>) -> bool {
+    brain.is_none_or(|brain| brain.wants_to_eat)
+}
+
+/// Whether an organism is willing to reproduce. Organisms without a brain always are.
+pub fn wants_to_reproduce(brain: Option<&Brain>) -> bool {
+    brain.is_none_or(|brain| brain.wants_to_reproduce)
+}
+
+/// Feeds what each organism with a brain senses through its network, steers it
+/// towards the velocity the network asks for and records its other decisions
+fn think(
+    mut organisms: Query<
+        (
+            Entity,
+            &Transform,
+            &Genome,
+            &Energy,
+            &Velocity,
+            &mut Brain,
+            &mut SteeringForce,
+        ),
+        With<Organism>,
+    >,
+    genomes: Query<&Genome, With<Organism>>,
+    candidates: Query<(&Genome, &Energy), With<Organism>>,
+    temperature: Res<TemperatureField>,
+    biomass: Res<BiomassField>,
+    index: Res<SpatialIndex>,
+    mode: Option<Res<BoundaryMode>>,
+    predation: Res<PredationConfig>,
+    mating: Res<MatingConfig>,
+) {
+    let sensing_radius = temperature.geometry().cell_size;
+    let wrap = boundary::world_wrap(mode.as_deref());
+
+    for (entity, transform, genome, energy, velocity, mut brain, mut force) in organisms.iter_mut()
+    {
+        let Some(network) = &genome.brain else {
+            continue;
+        };
+
+        let position = transform.translation.truncate();
+        let (threat, prey) =
+            predation.nearest_threat_and_prey(entity, position, genome, &genomes, &index);
+
+        let mate = mating::is_ready(genome, energy)
+            .then(|| mating.nearest_mate(entity, position, genome, &candidates, &index))
+            .flatten();
+
+        // Direction towards something seen within `radius`, stronger the closer it is
+        let proximity = |target: Option<Vec2>, radius: f32| {
+            target.map_or(Vec2::ZERO, |target| {
+                let offset = target - position;
+                offset.normalize_or_zero() * (1.0 - offset.length() / radius).max(0.0)
+            })
+        };
+
+        let temperature_deviation = temperature.sample(position).map_or(0.0, |current| {
+            ((current - genome.preferred_temperature) / TEMPERATURE_SCALE).clamp(-1.0, 1.0)
+        });
+        let temperature_gradient = temperature
+            .gradient(position, sensing_radius, wrap)
+            .normalize_or_zero();
+        let food = match genome.trophic_level {
+            TrophicLevel::Herbivore => biomass
+                .gradient(position, sensing_radius, wrap)
+                .normalize_or_zero(),
+            TrophicLevel::Carnivore => proximity(prey, predation.detection_radius()),
+        };
+        let threat = proximity(threat, predation.detection_radius());
+        let mate = proximity(mate, mating.search_radius());
+
+        let [move_x, move_y, eat, reproduce] = network.evaluate(&[
+            1.0,
+            temperature_deviation,
+            temperature_gradient.x,
+            temperature_gradient.y,
+            food.x,
+            food.y,
+            threat.x,
+            threat.y,
+            mate.x,
+            mate.y,
+            energy.0 / genome.reproduction_threshold,
+        ]);
+
+        let desired_velocity = Vec2::new(move_x, move_y).clamp_length_max(1.0) * genome.max_speed;
+        force.0 += desired_velocity - velocity.0;
+
+        brain.wants_to_eat = eat > 0.0;
+        brain.wants_to_reproduce = reproduce > 0.0;
+    }
+}
@@ -0,0 +1,53 @@
+use bevy::prelude::*;
+
+pub struct ClockPlugin {
+    /// Simulated seconds in one day
+    pub day_length: f32,
+    pub days_per_year: f32,
+}
+
+impl Plugin for ClockPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SimClock {
+            elapsed: 0.0,
+            day_length: self.day_length,
+            days_per_year: self.days_per_year,
+        })
+        .add_systems(FixedFirst, advance_clock);
+    }
+}
+
+/// Simulated time, advanced with every `FixedUpdate`
+#[derive(Resource, Debug)]
+pub struct SimClock {
+    /// Simulated seconds since the start
+    elapsed: f64,
+    day_length: f32,
+    days_per_year: f32,
+}
+
+impl SimClock {
+    /// Simulated seconds since the start
+    pub fn elapsed(&self) -> f64 {
+        self.elapsed
+    }
+
+    /// Days since the start, including the fraction of the current day
+    pub fn days(&self) -> f64 {
+        self.elapsed / self.day_length as f64
+    }
+
+    /// Fraction of the current day in [0, 1), where 0 is midnight and 0.5 is noon
+    pub fn time_of_day(&self) -> f32 {
+        self.days().fract() as f32
+    }
+
+    /// Fraction of the current year in [0, 1), where 0 is the spring equinox
+    pub fn time_of_year(&self) -> f32 {
+        (self.days() / self.days_per_year as f64).fract() as f32
+    }
+}
+
+fn advance_clock(mut clock: ResMut<SimClock>, time: Res<Time>) {
+    clock.elapsed += time.delta_seconds_f64();
+}
@@ -0,0 +1,463 @@
+use std::marker::PhantomData;
+
+use bevy::{prelude::*, tasks::ComputeTaskPool};
+
+use crate::boundary::{self, BoundaryMode};
+use crate::field::{Field, GridGeometry, Neighbor, ScalarField};
+use crate::overlay::{self, Overlay, OverlayImage};
+use crate::wind::WindField;
+
+pub const ROWS_PER_TASK: usize = 16; // Rows of the grid stepped by each parallel task
+
+/// A scalar field that spreads out over the grid by itself. Declare one by
+/// implementing this on a marker type and adding a `DiffusionPlugin` for it.
+pub trait DiffusingField: Send + Sync + 'static {
+    /// Identifies the field's overlay
+    const NAME: &'static str;
+    /// Fraction of the difference with each neighbor evened out per second
+    const DIFFUSIVITY: f32;
+    /// Fraction of the value lost per second
+    const DECAY: f32 = 0.0;
+    const MIN: f32 = 0.0;
+    const MAX: f32 = f32::MAX;
+    const INITIAL: f32 = 0.0;
+    /// Key that shows the field as the overlay
+    const OVERLAY_KEY: KeyCode;
+
+    /// Color of a cell with the given value in the overlay
+    fn color(value: f32) -> Color;
+
+    /// Axes along which the field wraps around to the opposite edge of the grid,
+    /// given those along which the world does. Nothing crosses the other edges.
+    fn wrap(world: BVec2) -> BVec2 {
+        world
+    }
+
+    /// Velocity of every cell carrying the field along, in world units per second.
+    /// Fields stay put unless they override this, usually with the wind.
+    fn flow(_wind: &WindField) -> Option<&Field<Vec2>> {
+        None
+    }
+}
+
+/// Adds storage, stepping and an overlay for a `DiffusingField`
+pub struct DiffusionPlugin<F>(PhantomData<F>);
+
+impl<F> Default for DiffusionPlugin<F> {
+    fn default() -> Self {
+        DiffusionPlugin(PhantomData)
+    }
+}
+
+impl<F: DiffusingField> Plugin for DiffusionPlugin<F> {
+    fn build(&self, app: &mut App) {
+        app.configure_sets(
+            FixedUpdate,
+            (DiffusionSet::Sources, DiffusionSet::Step).chain(),
+        )
+        .add_systems(Startup, setup::<F>)
+        .add_systems(FixedUpdate, step_field::<F>.in_set(DiffusionSet::Step))
+        .add_systems(
+            Update,
+            (
+                select_overlay::<F>,
+                visualize::<F>.run_if(resource_equals(Overlay::Field(F::NAME))),
+            ),
+        );
+    }
+}
+
+/// Stages of the update of all diffusing fields, run in order every `FixedUpdate`
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub enum DiffusionSet {
+    /// Add to the `Sources` of the fields
+    Sources,
+    Step,
+}
+
+/// Value of a diffusing field in every grid cell
+#[derive(Resource, Deref, DerefMut)]
+pub struct Concentration<F> {
+    #[deref]
+    values: ScalarField,
+    marker: PhantomData<F>,
+}
+
+/// Amount added to (or removed from, if negative) a diffusing field in each cell
+/// since its last step
+#[derive(Resource, Deref, DerefMut)]
+pub struct Sources<F> {
+    #[deref]
+    values: ScalarField,
+    marker: PhantomData<F>,
+}
+
+impl<F> Sources<F> {
+    /// Adds to the cell containing the given world position, if any
+    pub fn deposit(&mut self, position: Vec2, amount: f32) {
+        if let Some((x, y)) = self.values.geometry().world_to_cell(position) {
+            *self.values.get_mut(x, y) += amount;
+        }
+    }
+}
+
+/// Back buffer that each step of a diffusing field writes into
+#[derive(Resource)]
+struct Buffer<F> {
+    values: ScalarField,
+    marker: PhantomData<F>,
+}
+
+fn setup<F: DiffusingField>(mut commands: Commands, geometry: Res<GridGeometry>) {
+    let field = |value| ScalarField::new(*geometry, value);
+
+    commands.insert_resource(Concentration::<F> {
+        values: field(F::INITIAL),
+        marker: PhantomData,
+    });
+    commands.insert_resource(Sources::<F> {
+        values: field(0.0),
+        marker: PhantomData,
+    });
+    commands.insert_resource(Buffer::<F> {
+        values: field(0.0),
+        marker: PhantomData,
+    });
+}
+
+fn step_field<F: DiffusingField>(
+    mut concentration: ResMut<Concentration<F>>,
+    mut buffer: ResMut<Buffer<F>>,
+    mut sources: ResMut<Sources<F>>,
+    wind: Option<Res<WindField>>,
+    mode: Option<Res<BoundaryMode>>,
+    time: Res<Time>,
+) {
+    let flow = wind.as_deref().and_then(F::flow);
+    let wrap = F::wrap(boundary::world_wrap(mode.as_deref()));
+
+    advance::<F>(
+        &mut concentration.values,
+        &mut buffer.values,
+        &sources.values,
+        wrap,
+        flow,
+        time.delta_seconds(),
+        ROWS_PER_TASK,
+    );
+
+    sources.fill(0.0);
+}
+
+/// Advances a field wrapping around the axes set in `wrap` by `elapsed` seconds,
+/// using `buffer` as the back buffer, then adds the sources and keeps every cell
+/// within the field's bounds
+fn advance<F: DiffusingField>(
+    values: &mut ScalarField,
+    buffer: &mut ScalarField,
+    sources: &ScalarField,
+    wrap: BVec2,
+    flow: Option<&Field<Vec2>>,
+    elapsed: f32,
+    rows_per_task: usize,
+) {
+    let geometry = *values.geometry();
+    let flow_speed = flow.map_or(0.0, max_wind_speed);
+
+    // A cell may not exchange or lose more than all of its value in one step
+    let max_dt = 1.0 / (4.0 * (F::DIFFUSIVITY + flow_speed) + F::DECAY);
+
+    integrate(
+        values,
+        buffer,
+        elapsed,
+        max_dt,
+        rows_per_task,
+        &|values, x, y| {
+            let neighbors = geometry.neighbors(x, y, wrap);
+            let mut rate =
+                F::DIFFUSIVITY * laplacian(values, &neighbors, x, y) - F::DECAY * values.get(x, y);
+            if let Some(flow) = flow {
+                rate += advection(values, flow, &neighbors, x, y);
+            }
+            rate
+        },
+    );
+
+    for (value, source) in values.values_mut().iter_mut().zip(sources.values()) {
+        *value = (*value + source).clamp(F::MIN, F::MAX);
+    }
+}
+
+fn select_overlay<F: DiffusingField>(
+    mut overlay: ResMut<Overlay>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+) {
+    if keyboard_input.just_pressed(F::OVERLAY_KEY) {
+        *overlay = Overlay::Field(F::NAME);
+    }
+}
+
+fn visualize<F: DiffusingField>(
+    concentration: Res<Concentration<F>>,
+    overlay_image: Res<OverlayImage>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    overlay::paint(&mut images, &overlay_image, &concentration, F::color);
+}
+
+/// Writes the values after one explicit step of `dt` seconds into `output`. The
+/// rate of change of a cell in units per second is given by `rate`, called with the
+/// whole field and the cell's coordinates.
+///
+/// The grid is split into bands of `rows_per_task` rows that are stepped in
+/// parallel. Every cell is only read from `values` and written to `output`, so the
+/// result is identical no matter how the rows are split.
+pub fn step<R>(
+    values: &ScalarField,
+    output: &mut ScalarField,
+    dt: f32,
+    rows_per_task: usize,
+    rate: &R,
+) where
+    R: Fn(&ScalarField, usize, usize) -> f32 + Sync,
+{
+    let grid_width = values.width();
+    let step_rows = |rows: &mut [f32], first_row: usize| {
+        for (offset, value) in rows.iter_mut().enumerate() {
+            let x = offset % grid_width;
+            let y = first_row + offset / grid_width;
+
+            *value = values.get(x, y) + rate(values, x, y) * dt;
+        }
+    };
+
+    if rows_per_task >= values.height() {
+        step_rows(output.values_mut(), 0);
+        return;
+    }
+
+    let step_rows = &step_rows;
+    ComputeTaskPool::get().scope(|scope| {
+        let band_size = rows_per_task * grid_width;
+
+        for (band, rows) in output.values_mut().chunks_mut(band_size).enumerate() {
+            scope.spawn(async move { step_rows(rows, band * rows_per_task) });
+        }
+    });
+}
+
+/// Advances the values by `elapsed` seconds, using `buffer` as the back buffer.
+///
+/// Explicit steps are only stable up to some length, so if `elapsed` is longer
+/// than `max_dt` it is split into equal substeps.
+pub fn integrate<R>(
+    values: &mut ScalarField,
+    buffer: &mut ScalarField,
+    elapsed: f32,
+    max_dt: f32,
+    rows_per_task: usize,
+    rate: &R,
+) where
+    R: Fn(&ScalarField, usize, usize) -> f32 + Sync,
+{
+    let substeps = (elapsed / max_dt).ceil().max(1.0) as usize;
+    let dt = elapsed / substeps as f32;
+
+    for _ in 0..substeps {
+        step(values, buffer, dt, rows_per_task, rate);
+        std::mem::swap(values, buffer);
+    }
+}
+
+/// Sum of the differences between a cell and its neighbors. Multiplied by a
+/// diffusivity it is the rate at which the cell evens out with them.
+pub fn laplacian(values: &ScalarField, neighbors: &[Neighbor; 4], x: usize, y: usize) -> f32 {
+    let current = values.get(x, y);
+
+    neighbors
+        .iter()
+        .filter_map(|neighbor| neighbor.cell)
+        .map(|(neighbor_x, neighbor_y)| values.get(neighbor_x, neighbor_y) - current)
+        .sum()
+}
+
+/// Rate of change of a cell as the wind blows values across its faces.
+///
+/// The wind across the face between two cells is the average of theirs, and it
+/// carries the value of the upwind cell. Both cells see the same flux through a
+/// face, so what one gains the other loses and the total is conserved whether or
+/// not the wind is divergence free. Beyond an edge of the grid that doesn't wrap
+/// around, the world is taken to be like the cell on the edge: the cell's own
+/// wind blows its own value across, so values carried to the edge leave the grid
+/// instead of piling up against it.
+pub fn advection(
+    values: &ScalarField,
+    wind: &Field<Vec2>,
+    neighbors: &[Neighbor; 4],
+    x: usize,
+    y: usize,
+) -> f32 {
+    let cell_size = values.geometry().cell_size;
+    let current = values.get(x, y);
+    let current_wind = wind.get(x, y);
+
+    neighbors
+        .iter()
+        .map(|neighbor| {
+            let (face_wind, neighbor_value) = match neighbor.cell {
+                Some((neighbor_x, neighbor_y)) => (
+                    (current_wind + wind.get(neighbor_x, neighbor_y)) / 2.0,
+                    values.get(neighbor_x, neighbor_y),
+                ),
+                None => (current_wind, current),
+            };
+            // Cells per second blowing from the neighbor into the current cell
+            let inflow = -face_wind.dot(neighbor.edge.direction()) / cell_size;
+            let upwind = if inflow > 0.0 {
+                neighbor_value
+            } else {
+                current
+            };
+
+            inflow * upwind
+        })
+        .sum()
+}
+
+/// Fastest wind anywhere on the grid, in cells per second
+pub fn max_wind_speed(wind: &Field<Vec2>) -> f32 {
+    let cell_size = wind.geometry().cell_size;
+
+    wind.values()
+        .iter()
+        .map(|wind| wind.length() / cell_size)
+        .fold(0.0, f32::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::test_fields::{swirling_wind, test_grid};
+    use crate::wind;
+
+    /// Only spreads out
+    struct Still;
+
+    impl DiffusingField for Still {
+        const NAME: &'static str = "still";
+        const DIFFUSIVITY: f32 = 0.5;
+        const MAX: f32 = 100.0;
+        const OVERLAY_KEY: KeyCode = KeyCode::Digit0;
+
+        fn color(_value: f32) -> Color {
+            Color::BLACK
+        }
+    }
+
+    /// Spreads out and is blown around by the wind
+    struct Blown;
+
+    impl DiffusingField for Blown {
+        const NAME: &'static str = "blown";
+        const DIFFUSIVITY: f32 = 0.5;
+        const OVERLAY_KEY: KeyCode = KeyCode::Digit0;
+
+        fn color(_value: f32) -> Color {
+            Color::BLACK
+        }
+
+        fn flow(wind: &WindField) -> Option<&Field<Vec2>> {
+            Some(wind)
+        }
+    }
+
+    fn total(values: &ScalarField) -> f64 {
+        values.values().iter().map(|&value| value as f64).sum()
+    }
+
+    fn step<F: DiffusingField>(
+        values: &mut ScalarField,
+        sources: &ScalarField,
+        wind: &WindField,
+        wrap: BVec2,
+    ) {
+        let mut buffer = ScalarField::new(*values.geometry(), 0.0);
+        let rows_per_task = values.height();
+
+        advance::<F>(
+            values,
+            &mut buffer,
+            sources,
+            F::wrap(wrap),
+            F::flow(wind),
+            0.25,
+            rows_per_task,
+        );
+    }
+
+    #[test]
+    fn conserves_total_on_closed_grid() {
+        let mut values = test_grid(32, 24);
+        let sources = ScalarField::new(*values.geometry(), 0.0);
+        let wind = WindField(swirling_wind(*values.geometry()));
+        let initial = total(&values);
+
+        for _ in 0..50 {
+            step::<Still>(&mut values, &sources, &wind, BVec2::FALSE);
+        }
+
+        assert!((total(&values) - initial).abs() < initial * 1e-5);
+    }
+
+    #[test]
+    fn conserves_total_in_generated_wind() {
+        let mut values = test_grid(32, 24);
+        let sources = ScalarField::new(*values.geometry(), 0.0);
+        let wind = WindField(wind::test_wind(*values.geometry()));
+        let initial = total(&values);
+
+        for _ in 0..10 {
+            step::<Blown>(&mut values, &sources, &wind, BVec2::TRUE);
+        }
+
+        assert!((total(&values) - initial).abs() < initial * 1e-5);
+    }
+
+    #[test]
+    fn spreads_across_the_edges_of_a_wrapping_world() {
+        let geometry = *test_grid(8, 8).geometry();
+        let mut values = ScalarField::from_fn(geometry, |x, _| if x == 0 { 100.0 } else { 0.0 });
+        let sources = ScalarField::new(geometry, 0.0);
+        let calm = WindField(Field::new(geometry, Vec2::ZERO));
+
+        step::<Still>(&mut values, &sources, &calm, BVec2::new(true, false));
+
+        assert!(values.get(7, 4) > 0.0);
+        assert_eq!(values.get(6, 4), 0.0);
+    }
+
+    #[test]
+    fn sources_are_clamped_to_the_bounds() {
+        let mut values = test_grid(8, 8);
+        let sources =
+            ScalarField::from_fn(
+                *values.geometry(),
+                |x, _| {
+                    if x < 4 {
+                        -1000.0
+                    } else {
+                        1000.0
+                    }
+                },
+            );
+        let wind = WindField(swirling_wind(*values.geometry()));
+
+        step::<Still>(&mut values, &sources, &wind, BVec2::FALSE);
+
+        for y in 0..8 {
+            assert_eq!(values.get(0, y), Still::MIN);
+            assert_eq!(values.get(7, y), Still::MAX);
+        }
+    }
+}
@@ -0,0 +1,25 @@
+use std::{
+    fs::File,
+    io::{self, BufWriter, Write},
+    path::Path,
+};
+
+use bevy::prelude::*;
+
+/// Creates the file at `path` and fills it with `write`. Failures are logged
+/// rather than returned, naming `what` the file was meant to hold.
+pub fn write_file(
+    path: &Path,
+    what: &str,
+    write: impl FnOnce(&mut BufWriter<File>) -> io::Result<()>,
+) {
+    let result = File::create(path).and_then(|file| {
+        let mut out = BufWriter::new(file);
+        write(&mut out)?;
+        out.flush()
+    });
+
+    if let Err(error) = result {
+        error!("Failed to write the {what} to {}: {error}", path.display());
+    }
+}
@@ -0,0 +1,275 @@
+use bevy::prelude::*;
+
+/// Placement of a grid of square cells in the world
+#[derive(Resource, Debug, Default, Clone, Copy, PartialEq)]
+pub struct GridGeometry {
+    pub width: usize,
+    pub height: usize,
+    pub cell_size: f32,
+    /// World position of the bottom left corner of the first cell
+    pub origin: Vec2,
+}
+
+impl GridGeometry {
+    /// A grid centered in a world of the given size, with the first cell
+    /// centered on the bottom left corner of the world.
+    pub fn new(width: usize, height: usize, cell_size: f32, world_size: Vec2) -> Self {
+        let grid_size = Vec2::new(width as f32 * cell_size, height as f32 * cell_size);
+        let offset = (world_size - grid_size) / 2.0;
+
+        GridGeometry {
+            width,
+            height,
+            cell_size,
+            origin: offset - world_size / 2.0 - Vec2::splat(cell_size / 2.0),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.width * self.height
+    }
+
+    /// Row-major index of a cell
+    pub fn index(&self, x: usize, y: usize) -> usize {
+        y * self.width + x
+    }
+
+    /// World position of the center of a cell
+    pub fn cell_center(&self, x: usize, y: usize) -> Vec2 {
+        self.origin + (Vec2::new(x as f32, y as f32) + 0.5) * self.cell_size
+    }
+
+    /// The four neighbors of a cell, in the order west, east, south, north. Axes set
+    /// in `wrap` make the grid a torus, so the last row or column borders the first.
+    pub fn neighbors(&self, x: usize, y: usize, wrap: BVec2) -> [Neighbor; 4] {
+        let west = match x {
+            0 if wrap.x => Some(self.width - 1),
+            0 => None,
+            _ => Some(x - 1),
+        };
+        let east = match x + 1 {
+            next if next < self.width => Some(next),
+            _ if wrap.x => Some(0),
+            _ => None,
+        };
+        let south = match y {
+            0 if wrap.y => Some(self.height - 1),
+            0 => None,
+            _ => Some(y - 1),
+        };
+        let north = match y + 1 {
+            next if next < self.height => Some(next),
+            _ if wrap.y => Some(0),
+            _ => None,
+        };
+
+        [
+            Neighbor {
+                edge: Edge::West,
+                cell: west.map(|x| (x, y)),
+            },
+            Neighbor {
+                edge: Edge::East,
+                cell: east.map(|x| (x, y)),
+            },
+            Neighbor {
+                edge: Edge::South,
+                cell: south.map(|y| (x, y)),
+            },
+            Neighbor {
+                edge: Edge::North,
+                cell: north.map(|y| (x, y)),
+            },
+        ]
+    }
+
+    /// World space area covered by the grid
+    pub fn bounds(&self) -> Rect {
+        let size = Vec2::new(
+            self.width as f32 * self.cell_size,
+            self.height as f32 * self.cell_size,
+        );
+
+        Rect::from_corners(self.origin, self.origin + size)
+    }
+
+    /// Brings a position that left the grid across an edge along one of the axes
+    /// set in `wrap` back in from the opposite edge
+    pub fn wrap_position(&self, position: Vec2, wrap: BVec2) -> Vec2 {
+        let bounds = self.bounds();
+        let wrapped = bounds.min + (position - bounds.min).rem_euclid(bounds.size());
+
+        Vec2::select(wrap, wrapped, position)
+    }
+
+    /// Returns the cell containing the given world position, if any
+    pub fn world_to_cell(&self, position: Vec2) -> Option<(usize, usize)> {
+        let cell = ((position - self.origin) / self.cell_size).floor();
+
+        if cell.x < 0.0
+            || cell.y < 0.0
+            || cell.x >= self.width as f32
+            || cell.y >= self.height as f32
+        {
+            return None;
+        }
+
+        Some((cell.x as usize, cell.y as usize))
+    }
+
+    /// Returns the cell containing the given world position, or the closest
+    /// edge cell if the position is outside of the grid
+    pub fn world_to_cell_clamped(&self, position: Vec2) -> (usize, usize) {
+        let cell = ((position - self.origin) / self.cell_size).floor();
+
+        (
+            (cell.x.max(0.0) as usize).min(self.width.saturating_sub(1)),
+            (cell.y.max(0.0) as usize).min(self.height.saturating_sub(1)),
+        )
+    }
+}
+
+/// One of the four sides of a cell, or of the whole grid. North is towards the last row.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    West,
+    East,
+    South,
+    North,
+}
+
+impl Edge {
+    /// Unit vector pointing out of the cell across this edge
+    pub fn direction(&self) -> Vec2 {
+        match self {
+            Edge::West => Vec2::NEG_X,
+            Edge::East => Vec2::X,
+            Edge::South => Vec2::NEG_Y,
+            Edge::North => Vec2::Y,
+        }
+    }
+}
+
+/// The cell next to another across one of its edges
+#[derive(Debug, Clone, Copy)]
+pub struct Neighbor {
+    pub edge: Edge,
+    /// `None` where the cell lies on that edge of the grid and the grid doesn't wrap around
+    pub cell: Option<(usize, usize)>,
+}
+
+/// A value for every cell of a grid, stored contiguously in row-major order
+#[derive(Debug, Clone)]
+pub struct Field<T> {
+    geometry: GridGeometry,
+    values: Vec<T>,
+}
+
+/// A number for every cell of a grid
+pub type ScalarField = Field<f32>;
+
+impl<T: Copy> Field<T> {
+    pub fn new(geometry: GridGeometry, value: T) -> Self {
+        Field {
+            geometry,
+            values: vec![value; geometry.len()],
+        }
+    }
+
+    /// A field with each cell set by calling `f` with its coordinates
+    pub fn from_fn(geometry: GridGeometry, mut f: impl FnMut(usize, usize) -> T) -> Self {
+        let mut values = Vec::with_capacity(geometry.len());
+
+        for y in 0..geometry.height {
+            for x in 0..geometry.width {
+                values.push(f(x, y));
+            }
+        }
+
+        Field { geometry, values }
+    }
+
+    pub fn geometry(&self) -> &GridGeometry {
+        &self.geometry
+    }
+
+    pub fn width(&self) -> usize {
+        self.geometry.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.geometry.height
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> T {
+        self.values[self.geometry.index(x, y)]
+    }
+
+    pub fn get_mut(&mut self, x: usize, y: usize) -> &mut T {
+        let index = self.geometry.index(x, y);
+        &mut self.values[index]
+    }
+
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    pub fn values_mut(&mut self) -> &mut [T] {
+        &mut self.values
+    }
+
+    pub fn fill(&mut self, value: T) {
+        self.values.fill(value);
+    }
+
+    /// Value of the cell containing the given world position, if any
+    pub fn sample(&self, position: Vec2) -> Option<T> {
+        self.geometry
+            .world_to_cell(position)
+            .map(|(x, y)| self.get(x, y))
+    }
+}
+
+impl ScalarField {
+    /// Gradient per world unit at the given position, from central differences
+    /// `radius` away on each side. Positions across an edge along one of the axes
+    /// set in `wrap` are taken from the opposite side of the grid. Other samples off
+    /// the grid count as the value at the position, so there is no gradient when the
+    /// position itself is off the grid.
+    pub fn gradient(&self, position: Vec2, radius: f32, wrap: BVec2) -> Vec2 {
+        let position = self.geometry.wrap_position(position, wrap);
+        let Some(current) = self.sample(position) else {
+            return Vec2::ZERO;
+        };
+        let sample = |offset: Vec2| {
+            let position = self.geometry.wrap_position(position + offset, wrap);
+            self.sample(position).unwrap_or(current)
+        };
+
+        Vec2::new(
+            sample(Vec2::X * radius) - sample(Vec2::NEG_X * radius),
+            sample(Vec2::Y * radius) - sample(Vec2::NEG_Y * radius),
+        ) / (2.0 * radius)
+    }
+}
+
+/// Fields shared by the tests of the modules that work on grids
+#[cfg(test)]
+pub mod test_fields {
+    use super::*;
+
+    /// A grid of unit cells with values from 0 to 100 scattered over it
+    pub fn test_grid(width: usize, height: usize) -> ScalarField {
+        let geometry =
+            GridGeometry::new(width, height, 1.0, Vec2::new(width as f32, height as f32));
+
+        ScalarField::from_fn(geometry, |x, y| ((x * 37 + y * 61) % 100) as f32)
+    }
+
+    /// Wind swirling in every direction without piling up anywhere
+    pub fn swirling_wind(geometry: GridGeometry) -> Field<Vec2> {
+        Field::from_fn(geometry, |x, y| {
+            Vec2::new((y as f32 * 0.7).sin(), (x as f32 * 0.4).cos()) * 2.0
+        })
+    }
+}
@@ -0,0 +1,354 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::neat::{Innovations, NeuralNetwork};
+
+const MUTATION_RATE: f32 = 0.2; // Chance for each gene to mutate during reproduction
+const MUTATION_STRENGTH: f32 = 0.1; // Maximum mutation as a fraction of the gene's range
+const DIET_MUTATION_RATE: f32 = 0.005; // Chance for the offspring to switch trophic level
+const COMPATIBILITY_THRESHOLD: f32 = 0.5; // Organisms genetically closer than this can mate
+const BRAIN_DISTANCE_WEIGHT: f32 = 0.5; // Genetic distance per unit of compatibility distance between brains
+
+const SIZE_RANGE: (f32, f32) = (2.0, 12.0);
+const MAX_SPEED_RANGE: (f32, f32) = (1.0, 20.0);
+const MAX_FORCE_RANGE: (f32, f32) = (1.0, 50.0);
+const STEERING_WEIGHT_RANGE: (f32, f32) = (0.0, 3.0);
+const PREFERRED_TEMPERATURE_RANGE: (f32, f32) = (0.0, 100.0);
+const REPRODUCTION_THRESHOLD_RANGE: (f32, f32) = (120.0, 300.0);
+
+const HERBIVORE_COLOR: Color = Color::srgba(0.2, 0.8, 0.5, 0.6);
+const CARNIVORE_COLOR: Color = Color::srgba(0.85, 0.25, 0.2, 0.6);
+
+/// What an organism eats
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrophicLevel {
+    Herbivore,
+    Carnivore,
+}
+
+impl TrophicLevel {
+    pub fn base_color(&self) -> Color {
+        match self {
+            TrophicLevel::Herbivore => HERBIVORE_COLOR,
+            TrophicLevel::Carnivore => CARNIVORE_COLOR,
+        }
+    }
+}
+
+/// How strongly an organism responds to each of its steering behaviors
+#[derive(Clone, Copy, Debug)]
+pub struct SteeringWeights {
+    pub seek: f32,
+    pub flee: f32,
+    pub wander: f32,
+    pub separation: f32,
+    pub alignment: f32,
+    pub cohesion: f32,
+    pub obstacle_avoidance: f32,
+}
+
+impl SteeringWeights {
+    fn random() -> Self {
+        let mut rng = rand::thread_rng();
+        let mut around = |weight: f32| (weight + rng.gen_range(-0.2..0.2)).max(0.0);
+
+        SteeringWeights {
+            seek: around(1.0),
+            flee: around(2.0),
+            wander: around(0.3),
+            separation: around(1.5),
+            alignment: around(0.5),
+            cohesion: around(0.3),
+            obstacle_avoidance: around(2.0),
+        }
+    }
+
+    fn mutate(&self) -> Self {
+        let mutate = |weight| mutate_gene(weight, STEERING_WEIGHT_RANGE);
+
+        SteeringWeights {
+            seek: mutate(self.seek),
+            flee: mutate(self.flee),
+            wander: mutate(self.wander),
+            separation: mutate(self.separation),
+            alignment: mutate(self.alignment),
+            cohesion: mutate(self.cohesion),
+            obstacle_avoidance: mutate(self.obstacle_avoidance),
+        }
+    }
+
+    fn crossover(&self, other: &SteeringWeights, rng: &mut impl Rng) -> Self {
+        let mut pick = |a: f32, b: f32| if rng.gen() { a } else { b };
+
+        SteeringWeights {
+            seek: pick(self.seek, other.seek),
+            flee: pick(self.flee, other.flee),
+            wander: pick(self.wander, other.wander),
+            separation: pick(self.separation, other.separation),
+            alignment: pick(self.alignment, other.alignment),
+            cohesion: pick(self.cohesion, other.cohesion),
+            obstacle_avoidance: pick(self.obstacle_avoidance, other.obstacle_avoidance),
+        }
+    }
+
+    fn as_array(&self) -> [f32; 7] {
+        [
+            self.seek,
+            self.flee,
+            self.wander,
+            self.separation,
+            self.alignment,
+            self.cohesion,
+            self.obstacle_avoidance,
+        ]
+    }
+}
+
+/// Heritable traits from which an organism's phenotype is derived
+#[derive(Component, Clone, Debug)]
+pub struct Genome {
+    pub size: f32,
+    pub max_speed: f32,
+    /// Strongest steering force the organism can exert
+    pub max_force: f32,
+    pub steering: SteeringWeights,
+    pub preferred_temperature: f32,
+    pub reproduction_threshold: f32,
+    pub trophic_level: TrophicLevel,
+    /// Network deciding the organism's behavior in place of its steering weights
+    pub brain: Option<NeuralNetwork>,
+}
+
+impl Genome {
+    pub fn random(trophic_level: TrophicLevel) -> Self {
+        let mut rng = rand::thread_rng();
+
+        Genome {
+            size: rng.gen_range(4.0..8.0),
+            max_speed: rng.gen_range(4.0..12.0),
+            max_force: rng.gen_range(10.0..20.0),
+            steering: SteeringWeights::random(),
+            preferred_temperature: rng.gen_range(30.0..70.0),
+            reproduction_threshold: rng.gen_range(150.0..200.0),
+            trophic_level,
+            brain: None,
+        }
+    }
+
+    /// Returns a copy of this genome with random mutations applied
+    pub fn mutate(&self, innovations: &mut Innovations) -> Self {
+        Genome {
+            size: mutate_gene(self.size, SIZE_RANGE),
+            max_speed: mutate_gene(self.max_speed, MAX_SPEED_RANGE),
+            max_force: mutate_gene(self.max_force, MAX_FORCE_RANGE),
+            steering: self.steering.mutate(),
+            preferred_temperature: mutate_gene(
+                self.preferred_temperature,
+                PREFERRED_TEMPERATURE_RANGE,
+            ),
+            reproduction_threshold: mutate_gene(
+                self.reproduction_threshold,
+                REPRODUCTION_THRESHOLD_RANGE,
+            ),
+            trophic_level: mutate_trophic_level(self.trophic_level),
+            brain: self.brain.as_ref().map(|brain| brain.mutate(innovations)),
+        }
+    }
+
+    /// Offspring genome taking every gene from either parent at random. The
+    /// offspring's brain has the structure of the fitter parent's brain.
+    pub fn crossover(fitter: &Genome, other: &Genome) -> Self {
+        let mut rng = rand::thread_rng();
+        let steering = fitter.steering.crossover(&other.steering, &mut rng);
+        let mut pick = |a: f32, b: f32| if rng.gen() { a } else { b };
+
+        Genome {
+            size: pick(fitter.size, other.size),
+            max_speed: pick(fitter.max_speed, other.max_speed),
+            max_force: pick(fitter.max_force, other.max_force),
+            steering,
+            preferred_temperature: pick(fitter.preferred_temperature, other.preferred_temperature),
+            reproduction_threshold: pick(
+                fitter.reproduction_threshold,
+                other.reproduction_threshold,
+            ),
+            trophic_level: fitter.trophic_level,
+            brain: match (&fitter.brain, &other.brain) {
+                (Some(fitter), Some(other)) => Some(NeuralNetwork::crossover(fitter, other)),
+                _ => fitter.brain.clone(),
+            },
+        }
+    }
+
+    /// Euclidean distance between two genomes, with every gene scaled by its range.
+    /// Organisms of different trophic levels, or of which only one has a brain, are
+    /// infinitely far apart.
+    pub fn distance(&self, other: &Genome) -> f32 {
+        if self.trophic_level != other.trophic_level {
+            return f32::INFINITY;
+        }
+
+        let brain_distance = match (&self.brain, &other.brain) {
+            (Some(brain), Some(other)) => brain.compatibility_distance(other),
+            (None, None) => 0.0,
+            _ => return f32::INFINITY,
+        };
+
+        let gene = |a: f32, b: f32, (min, max): (f32, f32)| ((a - b) / (max - min)).powi(2);
+        let steering: f32 = self
+            .steering
+            .as_array()
+            .iter()
+            .zip(other.steering.as_array())
+            .map(|(&a, b)| gene(a, b, STEERING_WEIGHT_RANGE))
+            .sum();
+
+        (gene(self.size, other.size, SIZE_RANGE)
+            + gene(self.max_speed, other.max_speed, MAX_SPEED_RANGE)
+            + gene(self.max_force, other.max_force, MAX_FORCE_RANGE)
+            + gene(
+                self.preferred_temperature,
+                other.preferred_temperature,
+                PREFERRED_TEMPERATURE_RANGE,
+            )
+            + gene(
+                self.reproduction_threshold,
+                other.reproduction_threshold,
+                REPRODUCTION_THRESHOLD_RANGE,
+            )
+            + steering
+            + (brain_distance * BRAIN_DISTANCE_WEIGHT).powi(2))
+        .sqrt()
+    }
+
+    /// Whether two organisms are genetically close enough to mate
+    pub fn is_compatible(&self, other: &Genome) -> bool {
+        self.distance(other) < COMPATIBILITY_THRESHOLD
+    }
+
+    /// Body area relative to an organism of size 4
+    pub fn body_mass(&self) -> f32 {
+        (self.size / 4.0).powi(2)
+    }
+}
+
+pub fn mutate_gene(value: f32, (min, max): (f32, f32)) -> f32 {
+    let mut rng = rand::thread_rng();
+
+    if rng.gen::<f32>() >= MUTATION_RATE {
+        return value;
+    }
+
+    let delta = rng.gen_range(-1.0..1.0) * MUTATION_STRENGTH * (max - min);
+    (value + delta).clamp(min, max)
+}
+
+fn mutate_trophic_level(trophic_level: TrophicLevel) -> TrophicLevel {
+    if rand::random::<f32>() >= DIET_MUTATION_RATE {
+        return trophic_level;
+    }
+
+    match trophic_level {
+        TrophicLevel::Herbivore => TrophicLevel::Carnivore,
+        TrophicLevel::Carnivore => TrophicLevel::Herbivore,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_brain(trophic_level: TrophicLevel) -> Genome {
+        Genome {
+            brain: Some(NeuralNetwork::random()),
+            ..Genome::random(trophic_level)
+        }
+    }
+
+    #[test]
+    fn distance_is_symmetric_and_zero_to_itself() {
+        for _ in 0..20 {
+            for (a, b) in [
+                (
+                    Genome::random(TrophicLevel::Herbivore),
+                    Genome::random(TrophicLevel::Herbivore),
+                ),
+                (
+                    with_brain(TrophicLevel::Carnivore),
+                    with_brain(TrophicLevel::Carnivore),
+                ),
+            ] {
+                assert_eq!(a.distance(&a), 0.0);
+                assert!((a.distance(&b) - b.distance(&a)).abs() < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn distance_is_infinite_between_unrelated_genomes() {
+        let herbivore = Genome::random(TrophicLevel::Herbivore);
+        let carnivore = Genome {
+            trophic_level: TrophicLevel::Carnivore,
+            ..herbivore.clone()
+        };
+        let thinker = Genome {
+            brain: Some(NeuralNetwork::random()),
+            ..herbivore.clone()
+        };
+
+        assert_eq!(herbivore.distance(&carnivore), f32::INFINITY);
+        assert_eq!(herbivore.distance(&thinker), f32::INFINITY);
+        assert_eq!(thinker.distance(&herbivore), f32::INFINITY);
+        assert!(!herbivore.is_compatible(&thinker));
+    }
+
+    #[test]
+    fn genomes_are_compatible_below_the_threshold() {
+        let genome = Genome::random(TrophicLevel::Herbivore);
+        let size_range = SIZE_RANGE.1 - SIZE_RANGE.0;
+        let with_size_offset = |fraction: f32| Genome {
+            size: genome.size + COMPATIBILITY_THRESHOLD * fraction * size_range,
+            ..genome.clone()
+        };
+
+        assert!(genome.is_compatible(&genome));
+        assert!(genome.is_compatible(&with_size_offset(0.9)));
+        assert!(with_size_offset(0.9).is_compatible(&genome));
+        assert!(!genome.is_compatible(&with_size_offset(1.1)));
+        assert!(!with_size_offset(1.1).is_compatible(&genome));
+    }
+
+    #[test]
+    fn crossover_takes_every_gene_from_a_parent() {
+        for _ in 0..20 {
+            let fitter = with_brain(TrophicLevel::Herbivore);
+            let other = Genome {
+                brain: fitter.brain.clone(),
+                ..Genome::random(TrophicLevel::Carnivore)
+            };
+            let offspring = Genome::crossover(&fitter, &other);
+
+            let from_parent = |gene: fn(&Genome) -> f32| {
+                gene(&offspring) == gene(&fitter) || gene(&offspring) == gene(&other)
+            };
+            assert!(from_parent(|genome| genome.size));
+            assert!(from_parent(|genome| genome.max_speed));
+            assert!(from_parent(|genome| genome.max_force));
+            assert!(from_parent(|genome| genome.preferred_temperature));
+            assert!(from_parent(|genome| genome.reproduction_threshold));
+            for (i, weight) in offspring.steering.as_array().into_iter().enumerate() {
+                assert!(
+                    weight == fitter.steering.as_array()[i]
+                        || weight == other.steering.as_array()[i]
+                );
+            }
+            assert_eq!(offspring.trophic_level, fitter.trophic_level);
+
+            let brain = offspring.brain.as_ref().unwrap();
+            assert_eq!(
+                brain.compatibility_distance(fitter.brain.as_ref().unwrap()),
+                0.0
+            );
+        }
+    }
+}
@@ -1,217 +1,713 @@
 use bevy::prelude::*;
 use noise::{NoiseFn, Perlin};
 
-const INITIAL_TEMPERATURE: f32 = 50.0;
-const TILE_MASS: f32 = 0.5;
+use crate::boundary::{self, BoundaryMode};
+use crate::diffusion;
+use crate::field::{Edge, Field, GridGeometry, Neighbor, ScalarField};
+use crate::material::{Material, MaterialField};
+use crate::overlay::{self, Overlay, OverlayImage};
+use crate::wind::WindField;
+
 const HEAT_TRANSFER_SPEED: f32 = 1.0;
-const TILE_HEAT_CAPACITY: f32 = 1.0;
-const MINIMUM_HEAT: f32 = 0.0;
-const MAXIMUM_HEAT: f32 = 100.0;
-const CHUNK_SIZE: usize = 16; // Make sure this is divisible by grid_width and grid_height
-const CHUNK_CONSTANT: usize = 256;
-const CHUNKING_FACTOR: f32 = (CHUNK_CONSTANT as f32 / (CHUNK_SIZE.pow(2) as f32)) as f32;
+const AIR_HEAT_CAPACITY: f32 = 0.5; // Heat carried per degree by the air blowing through a cell
+const MAXIMUM_HEAT: f32 = 100.0; // Temperature shown as fully red
 
 pub struct HeatDiffusionPlugin {
     pub world_size: Vec2,
     pub grid_width: usize,
     pub grid_height: usize,
     pub cell_size: f32,
+    pub boundaries: ThermalBoundaries,
 }
 
 impl Plugin for HeatDiffusionPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(HeatDiffusionConfig {
-            grid_width: self.grid_width,
-            grid_height: self.grid_height,
-            cell_size: self.cell_size,
-            world_size: self.world_size,
-        })
-        .insert_resource(CurrentChunk { x: 0, y: 0 })
-        .insert_resource(HeatFluxGrid {
-            grid: vec![vec![0.0; self.grid_height]; self.grid_width],
-        })
-        .insert_resource(ProcessedTileCount(0))
-        .add_systems(Startup, setup)
-        .add_systems(
-            FixedUpdate,
-            (calculate_heat_diffusion, apply_heat_diffusion).chain(),
-        )
-        .add_systems(Update, visualize_temperature);
+        assert_eq!(
+            self.boundaries.west == ThermalBoundary::Periodic,
+            self.boundaries.east == ThermalBoundary::Periodic,
+            "periodic thermal boundaries must be set on both the west and east edges"
+        );
+        assert_eq!(
+            self.boundaries.south == ThermalBoundary::Periodic,
+            self.boundaries.north == ThermalBoundary::Periodic,
+            "periodic thermal boundaries must be set on both the south and north edges"
+        );
+        // Organisms wrap around both axes or neither, so the heat grid must do the same
+        assert!(
+            self.boundaries.wrap().all() || !self.boundaries.wrap().any(),
+            "periodic thermal boundaries must be set on all four edges or none, \
+             since organisms cannot wrap around a single axis"
+        );
+        if let Some(&mode) = app.world().get_resource::<BoundaryMode>() {
+            boundary::check_wrap_matches(mode, &self.boundaries);
+        }
+
+        let geometry = GridGeometry::new(
+            self.grid_width,
+            self.grid_height,
+            self.cell_size,
+            self.world_size,
+        );
+
+        // Plain ground and still air everywhere, unless they are shaped by other plugins
+        app.world_mut()
+            .get_resource_or_insert_with(|| MaterialField(Field::new(geometry, Material::Soil)));
+        app.world_mut()
+            .get_resource_or_insert_with(|| WindField(Field::new(geometry, Vec2::ZERO)));
+
+        app.insert_resource(self.boundaries)
+            .insert_resource(geometry)
+            .insert_resource(TemperatureField(initial_temperatures(geometry)))
+            .insert_resource(LapseCooling(ScalarField::new(geometry, 0.0)))
+            .insert_resource(HeatSources(ScalarField::new(geometry, 0.0)))
+            .insert_resource(DiffusionBuffer(ScalarField::new(geometry, 0.0)))
+            .configure_sets(
+                FixedUpdate,
+                (HeatDiffusionSet::Sources, HeatDiffusionSet::Diffuse).chain(),
+            )
+            .add_systems(FixedUpdate, diffuse_heat.in_set(HeatDiffusionSet::Diffuse))
+            .add_systems(
+                Update,
+                visualize_temperature.run_if(resource_equals(Overlay::Temperature)),
+            );
     }
 }
 
-#[derive(Resource)]
-struct HeatDiffusionConfig {
-    grid_width: usize,
-    grid_height: usize,
-    cell_size: f32,
-    world_size: Vec2,
+/// Condition applied to heat exchange across one edge of the grid
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ThermalBoundary {
+    /// No heat crosses the edge (Neumann)
+    #[default]
+    Insulated,
+    /// The edge is held at a constant temperature (Dirichlet)
+    FixedTemperature(f32),
+    /// The edge borders the opposite edge. Either all four edges are periodic or none are.
+    Periodic,
+    /// Heat is exchanged with an ambient temperature in proportion to the difference
+    Radiative {
+        ambient_temperature: f32,
+        coefficient: f32,
+    },
 }
 
-#[derive(Component)]
-struct Temperature(f32);
+impl ThermalBoundary {
+    /// Heat flux into an edge cell of the given material and temperature
+    fn heat_flux(&self, material: Material, temperature: f32) -> f32 {
+        match *self {
+            ThermalBoundary::Insulated | ThermalBoundary::Periodic => 0.0,
+            ThermalBoundary::FixedTemperature(fixed_temperature) => {
+                // The edge behaves like a neighbor of the same material
+                material.thermal_conductivity(temperature) * (fixed_temperature - temperature)
+            }
+            ThermalBoundary::Radiative {
+                ambient_temperature,
+                coefficient,
+            } => coefficient * (ambient_temperature - temperature),
+        }
+    }
+}
 
-#[derive(Component)]
-struct GridPosition {
-    x: usize,
-    y: usize,
+/// Thermal boundary condition for each edge of the grid. North is the top (last) row.
+#[derive(Resource, Debug, Default, Clone, Copy)]
+pub struct ThermalBoundaries {
+    pub north: ThermalBoundary,
+    pub south: ThermalBoundary,
+    pub east: ThermalBoundary,
+    pub west: ThermalBoundary,
 }
 
-#[derive(Resource)]
-struct CurrentChunk {
-    x: usize,
-    y: usize,
+impl ThermalBoundaries {
+    /// Axes along which the grid wraps around to the opposite edge
+    pub fn wrap(&self) -> BVec2 {
+        BVec2::new(
+            self.west == ThermalBoundary::Periodic,
+            self.south == ThermalBoundary::Periodic,
+        )
+    }
+
+    fn get(&self, edge: Edge) -> &ThermalBoundary {
+        match edge {
+            Edge::West => &self.west,
+            Edge::East => &self.east,
+            Edge::South => &self.south,
+            Edge::North => &self.north,
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = &ThermalBoundary> {
+        [&self.north, &self.south, &self.east, &self.west].into_iter()
+    }
 }
 
-#[derive(Resource)]
-struct HeatFluxGrid {
-    grid: Vec<Vec<f32>>,
+/// Stages of the heat update, run in order every `FixedUpdate`
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub enum HeatDiffusionSet {
+    /// Add heat produced or absorbed on cells to the `HeatSources`
+    Sources,
+    Diffuse,
 }
 
+/// Temperature of every grid cell
+#[derive(Resource, Deref, DerefMut)]
+pub struct TemperatureField(pub ScalarField);
+
+/// Degrees each cell is kept colder than the cells around it, such as by the
+/// lapse rate on high ground.
+///
+/// Heat conducts and is carried by the wind as if every cell were warmer by this
+/// much, i.e. at its potential temperature, so diffusion evens out potential
+/// temperatures and leaves the cooling in place.
+#[derive(Resource, Deref, DerefMut)]
+pub struct LapseCooling(pub ScalarField);
+
+/// Heat added (or removed, if negative) on each cell since the last diffusion step
+#[derive(Resource, Deref, DerefMut)]
+pub struct HeatSources(ScalarField);
+
+impl HeatSources {
+    pub fn add_heat(&mut self, x: usize, y: usize, heat: f32) {
+        *self.get_mut(x, y) += heat;
+    }
+}
+
+/// Back buffer that each diffusion step writes the new temperatures into
 #[derive(Resource)]
-struct ProcessedTileCount(usize);
+struct DiffusionBuffer(ScalarField);
 
-fn setup(mut commands: Commands, config: Res<HeatDiffusionConfig>) {
+fn initial_temperatures(geometry: GridGeometry) -> ScalarField {
     let perlin = Perlin::new(rand::random::<u32>());
     let scale = 0.1;
 
-    let offset_x = (config.world_size.x - config.grid_width as f32 * config.cell_size) / 2.0;
-    let offset_y = (config.world_size.y - config.grid_height as f32 * config.cell_size) / 2.0;
-
-    for x in 0..config.grid_width {
-        for y in 0..config.grid_height {
-            let noise_value = perlin.get([x as f64 * scale, y as f64 * scale]);
-            let temperature = ((noise_value + 1.0) / 2.0) * 100.0; // Normalize to [0, 100]
-
-            commands.spawn((
-                GridPosition { x, y },
-                Temperature(temperature as f32),
-                SpriteBundle {
-                    sprite: Sprite {
-                        custom_size: Some(Vec2::new(config.cell_size, config.cell_size)),
-                        ..Default::default()
-                    },
-                    transform: Transform::from_translation(Vec3::new(
-                        x as f32 * config.cell_size + offset_x - config.world_size.x / 2.0,
-                        y as f32 * config.cell_size + offset_y - config.world_size.y / 2.0,
-                        0.0,
-                    )),
-                    ..Default::default()
-                },
-            ));
-        }
+    ScalarField::from_fn(geometry, |x, y| {
+        let noise_value = perlin.get([x as f64 * scale, y as f64 * scale]);
+        (((noise_value + 1.0) / 2.0) * 100.0) as f32 // Normalize to [0, 100]
+    })
+}
+
+/// Conductance of the interface between cells `a` and `b`.
+///
+/// The two cells conduct in series, so the conductance of their interface is the
+/// harmonic mean of each material's conductivity at its own temperature. Swapping
+/// the cells gives exactly the same result.
+fn interface_conductance(
+    material_a: Material,
+    temperature_a: f32,
+    material_b: Material,
+    temperature_b: f32,
+) -> f32 {
+    let conductivity_a = material_a.thermal_conductivity(temperature_a);
+    let conductivity_b = material_b.thermal_conductivity(temperature_b);
+    let total_conductivity = conductivity_a + conductivity_b;
+    if total_conductivity <= 0.0 {
+        return 0.0;
     }
+
+    2.0 * conductivity_a * conductivity_b / total_conductivity
 }
 
-fn calculate_heat_flux(temp1: f32, temp2: f32) -> f32 {
-    let temp_mid = (temp1 + temp2) / 2.0;
-    let thermal_conductivity = 0.6065 - 0.00122 * temp_mid + 0.0000063 * temp_mid.powi(2);
+/// Everything besides the temperatures that decides how heat moves around the grid
+struct Medium<'a> {
+    materials: &'a Field<Material>,
+    /// Wind velocity of every cell, in world units per second
+    wind: &'a Field<Vec2>,
+    boundaries: &'a ThermalBoundaries,
+    /// See `LapseCooling`
+    cooling: &'a ScalarField,
+}
 
-    thermal_conductivity * (temp1 - temp2)
+impl Medium<'_> {
+    /// Temperature of a cell brought down to sea level, which is what heat evens out
+    fn potential_temperature(&self, temperatures: &ScalarField, x: usize, y: usize) -> f32 {
+        temperatures.get(x, y) + self.cooling.get(x, y)
+    }
+
+    fn neighbors(&self, x: usize, y: usize) -> [Neighbor; 4] {
+        self.materials
+            .geometry()
+            .neighbors(x, y, self.boundaries.wrap())
+    }
 }
 
-fn calculate_heat_diffusion(
-    query: Query<(&GridPosition, &mut Temperature)>,
-    mut current_chunk: ResMut<CurrentChunk>,
-    mut heat_flux_grid: ResMut<HeatFluxGrid>,
-    mut processed_tile_count: ResMut<ProcessedTileCount>,
-    config: Res<HeatDiffusionConfig>,
-) {
-    let mut temperature_grid = vec![vec![0.0; config.grid_height]; config.grid_width];
-    for (pos, temperature) in query.iter() {
-        temperature_grid[pos.x][pos.y] = temperature.0;
-    }
-
-    // Calculate the starting and ending indices for the current chunk
-    let start_x = current_chunk.x * CHUNK_SIZE;
-    let start_y = current_chunk.y * CHUNK_SIZE;
-    let end_x = (start_x + CHUNK_SIZE).min(config.grid_width);
-    let end_y = (start_y + CHUNK_SIZE).min(config.grid_height);
-
-    // Iterate over each cell in the chunk
-    for x in start_x..end_x {
-        for y in start_y..end_y {
-            let current_temp = temperature_grid[x][y];
-
-            // Check the neighboring cells in the east and south directions
-            for (dx, dy) in [(1, 0), (0, 1)].iter() {
-                let neighbor_x = x as isize + dx;
-                let neighbor_y = y as isize + dy;
-
-                if neighbor_x >= 0
-                    && neighbor_x < config.grid_width as isize
-                    && neighbor_y >= 0
-                    && neighbor_y < config.grid_height as isize
-                {
-                    let neighbor_temp = temperature_grid[neighbor_x as usize][neighbor_y as usize];
-
-                    // Calculate the heat flux between the current cell and its neighbor
-                    let flux = calculate_heat_flux(current_temp, neighbor_temp) * CHUNKING_FACTOR;
-
-                    // Update the heat flux grid resource for both the current cell and the neighbor
-                    heat_flux_grid.grid[x][y] -= flux;
-                    heat_flux_grid.grid[neighbor_x as usize][neighbor_y as usize] += flux;
-                }
+/// Net heat conducted into a cell from its four neighbors and any grid edges it lies on.
+///
+/// The flux between two cells is computed from the same pair of temperatures on
+/// both sides, so what one cell gains its neighbor loses and no heat is created
+/// or destroyed inside the grid. Heat only enters or leaves through the edges.
+/// It flows down differences in potential temperature, so cells kept colder by
+/// their `LapseCooling` stay that much colder than their neighbors. Edges
+/// exchange heat with the actual temperature of the cell, so a fixed edge pulls
+/// the cells along it towards its temperature whatever their altitude.
+fn net_heat_flux(
+    temperatures: &ScalarField,
+    medium: &Medium,
+    neighbors: &[Neighbor; 4],
+    x: usize,
+    y: usize,
+) -> f32 {
+    let current_temp = temperatures.get(x, y);
+    let current_potential = medium.potential_temperature(temperatures, x, y);
+    let current_material = medium.materials.get(x, y);
+
+    neighbors
+        .iter()
+        .map(|neighbor| match neighbor.cell {
+            Some((neighbor_x, neighbor_y)) => {
+                let conductance = interface_conductance(
+                    medium.materials.get(neighbor_x, neighbor_y),
+                    temperatures.get(neighbor_x, neighbor_y),
+                    current_material,
+                    current_temp,
+                );
+                let neighbor_potential =
+                    medium.potential_temperature(temperatures, neighbor_x, neighbor_y);
+
+                conductance * (neighbor_potential - current_potential)
             }
+            None => medium
+                .boundaries
+                .get(neighbor.edge)
+                .heat_flux(current_material, current_temp),
+        })
+        .sum()
+}
+
+/// Degrees per second a cell warms (or cools, if negative) by conduction and by
+/// the wind blowing in air from its neighbors. Air warms or cools with the
+/// height it descends or climbs on the way, so it carries potential temperature.
+fn heating_rate(temperatures: &ScalarField, medium: &Medium, x: usize, y: usize) -> f32 {
+    let neighbors = medium.neighbors(x, y);
+    let advection = diffusion::advection(temperatures, medium.wind, &neighbors, x, y)
+        + diffusion::advection(medium.cooling, medium.wind, &neighbors, x, y);
+    let heat = net_heat_flux(temperatures, medium, &neighbors, x, y) * HEAT_TRANSFER_SPEED
+        + AIR_HEAT_CAPACITY * advection;
+
+    heat / medium.materials.get(x, y).tile_heat_capacity()
+}
+
+/// Largest time step for which an explicit update of the given temperatures is stable.
+///
+/// Each cell exchanges heat with four neighbors or edges, so the update only stays
+/// within the range of the current temperatures while the heat it exchanges per
+/// degree in one step, `4 * (k * speed + air * wind) * dt`, is at most its heat
+/// capacity `m * c`.
+/// This uses the most conductive and least heat retaining of all materials.
+fn stable_time_step(temperatures: &ScalarField, medium: &Medium) -> f32 {
+    let boundaries = medium.boundaries;
+    let fixed_temperatures = boundaries.iter().filter_map(|boundary| match boundary {
+        ThermalBoundary::FixedTemperature(temperature) => Some(temperature),
+        _ => None,
+    });
+
+    let (min, max) = temperatures
+        .values()
+        .iter()
+        .chain(fixed_temperatures)
+        .fold((f32::MAX, f32::MIN), |(min, max), &temperature| {
+            (min.min(temperature), max.max(temperature))
+        });
+
+    // Conductivities are at most quadratic, so their maximum over a range is at one of the ends
+    let material_conductivities = Material::all().into_iter().flat_map(|material| {
+        [
+            material.thermal_conductivity(min),
+            material.thermal_conductivity(max),
+        ]
+    });
+    let radiative_coefficients = boundaries.iter().filter_map(|boundary| match boundary {
+        ThermalBoundary::Radiative { coefficient, .. } => Some(*coefficient),
+        _ => None,
+    });
+    let max_conductivity = material_conductivities
+        .chain(radiative_coefficients)
+        .fold(0.0, f32::max);
+
+    let min_heat_capacity = Material::all()
+        .iter()
+        .map(Material::tile_heat_capacity)
+        .fold(f32::MAX, f32::min);
+
+    let max_outflow = max_conductivity * HEAT_TRANSFER_SPEED
+        + AIR_HEAT_CAPACITY * diffusion::max_wind_speed(medium.wind);
+
+    min_heat_capacity / (4.0 * max_outflow)
+}
+
+/// Advances the temperatures by `elapsed` seconds, using `buffer` as the back buffer
+fn integrate_heat_diffusion(
+    temperatures: &mut ScalarField,
+    medium: &Medium,
+    buffer: &mut ScalarField,
+    elapsed: f32,
+    rows_per_task: usize,
+) {
+    let max_dt = stable_time_step(temperatures, medium);
+
+    diffusion::integrate(
+        temperatures,
+        buffer,
+        elapsed,
+        max_dt,
+        rows_per_task,
+        &|temperatures, x, y| heating_rate(temperatures, medium, x, y),
+    );
+}
+
+fn diffuse_heat(
+    mut temperature: ResMut<TemperatureField>,
+    mut buffer: ResMut<DiffusionBuffer>,
+    mut sources: ResMut<HeatSources>,
+    materials: Res<MaterialField>,
+    wind: Res<WindField>,
+    boundaries: Res<ThermalBoundaries>,
+    cooling: Res<LapseCooling>,
+    time: Res<Time>,
+) {
+    let medium = Medium {
+        materials: &materials,
+        wind: &wind,
+        boundaries: &boundaries,
+        cooling: &cooling,
+    };
+
+    integrate_heat_diffusion(
+        &mut temperature,
+        &medium,
+        &mut buffer.0,
+        time.delta_seconds(),
+        diffusion::ROWS_PER_TASK,
+    );
+
+    // Add the heat from sources
+    for ((temperature, heat), material) in temperature
+        .values_mut()
+        .iter_mut()
+        .zip(sources.values())
+        .zip(materials.values())
+    {
+        *temperature += heat / material.tile_heat_capacity();
+    }
+
+    sources.fill(0.0);
+}
+
+fn visualize_temperature(
+    temperature: Res<TemperatureField>,
+    overlay_image: Res<OverlayImage>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    overlay::paint(&mut images, &overlay_image, &temperature, |temperature| {
+        let temperature_ratio = (temperature / MAXIMUM_HEAT).clamp(0.0, 1.0);
+
+        Color::srgb(temperature_ratio, 0.0, 1.0 - temperature_ratio)
+    });
+}
 
-            processed_tile_count.0 += 1;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::field::test_fields::{swirling_wind, test_grid};
+    use crate::wind;
+    use bevy::tasks::{ComputeTaskPool, TaskPool};
+
+    fn all_edges(boundary: ThermalBoundary) -> ThermalBoundaries {
+        ThermalBoundaries {
+            north: boundary,
+            south: boundary,
+            east: boundary,
+            west: boundary,
         }
     }
 
-    // Move to the next chunk, wrapping around if necessary
-    current_chunk.x += 1;
-    if current_chunk.x * CHUNK_SIZE >= config.grid_width {
-        current_chunk.x = 0;
-        current_chunk.y += 1;
-        if current_chunk.y * CHUNK_SIZE >= config.grid_height {
-            current_chunk.y = 0;
+    /// A mix of every material, so that most neighbors differ
+    fn test_materials(temperatures: &ScalarField) -> Field<Material> {
+        let materials = Material::all();
+
+        Field::from_fn(*temperatures.geometry(), |x, y| {
+            materials[(x * 3 + y * 7) % materials.len()]
+        })
+    }
+
+    fn total_heat(temperatures: &ScalarField, materials: &Field<Material>) -> f64 {
+        temperatures
+            .values()
+            .iter()
+            .zip(materials.values())
+            .map(|(&t, material)| (t * material.tile_heat_capacity()) as f64)
+            .sum()
+    }
+
+    fn step(
+        temperatures: &mut ScalarField,
+        materials: &Field<Material>,
+        elapsed: f32,
+        boundary: ThermalBoundary,
+    ) {
+        let calm = Field::new(*temperatures.geometry(), Vec2::ZERO);
+
+        step_in_wind(temperatures, materials, &calm, elapsed, boundary);
+    }
+
+    fn step_in_wind(
+        temperatures: &mut ScalarField,
+        materials: &Field<Material>,
+        wind: &Field<Vec2>,
+        elapsed: f32,
+        boundary: ThermalBoundary,
+    ) {
+        let mut buffer = ScalarField::new(*temperatures.geometry(), 0.0);
+        let boundaries = all_edges(boundary);
+        let cooling = ScalarField::new(*materials.geometry(), 0.0);
+        let medium = Medium {
+            materials,
+            wind,
+            boundaries: &boundaries,
+            cooling: &cooling,
+        };
+        let rows_per_task = temperatures.height();
+
+        integrate_heat_diffusion(temperatures, &medium, &mut buffer, elapsed, rows_per_task);
+    }
+
+    #[test]
+    fn conserves_heat_on_closed_grid() {
+        let mut temperatures = test_grid(32, 24);
+        let materials = Field::new(*temperatures.geometry(), Material::Water);
+        let initial = total_heat(&temperatures, &materials);
+
+        for _ in 0..50 {
+            step(
+                &mut temperatures,
+                &materials,
+                0.25,
+                ThermalBoundary::Insulated,
+            );
         }
+
+        assert!((total_heat(&temperatures, &materials) - initial).abs() < initial * 1e-5);
     }
-}
 
-fn apply_heat_diffusion(
-    mut query: Query<(&GridPosition, &mut Temperature)>,
-    time: Res<Time>,
-    mut heat_flux_grid: ResMut<HeatFluxGrid>,
-    mut processed_tile_count: ResMut<ProcessedTileCount>,
-    config: Res<HeatDiffusionConfig>,
-) {
-    // Only apply heat diffusion if we are processing the first chunk (meaning that a full cycle has been completed)
-    if processed_tile_count.0 != config.grid_width * config.grid_height {
-        return;
+    #[test]
+    fn conserves_heat_on_periodic_grid() {
+        let mut temperatures = test_grid(32, 24);
+        let materials = Field::new(*temperatures.geometry(), Material::Water);
+        let initial = total_heat(&temperatures, &materials);
+
+        for _ in 0..50 {
+            step(
+                &mut temperatures,
+                &materials,
+                0.25,
+                ThermalBoundary::Periodic,
+            );
+        }
+
+        assert!((total_heat(&temperatures, &materials) - initial).abs() < initial * 1e-5);
     }
 
-    // Prepare a grid to store the new temperatures
-    let mut new_temperatures: Vec<Vec<f32>> =
-        vec![vec![INITIAL_TEMPERATURE; config.grid_height]; config.grid_width];
+    #[test]
+    fn conserves_heat_across_mixed_materials() {
+        let mut temperatures = test_grid(32, 24);
+        let materials = test_materials(&temperatures);
+        let initial = total_heat(&temperatures, &materials);
+
+        for _ in 0..50 {
+            step(
+                &mut temperatures,
+                &materials,
+                0.25,
+                ThermalBoundary::Periodic,
+            );
+        }
 
-    // Calculate the new temperature for each cell based on the heat flux
-    for (pos, temp) in query.iter() {
-        let heat_flux = heat_flux_grid.grid[pos.x][pos.y];
-        let new_temp = temp.0
-            + (heat_flux / (TILE_MASS * TILE_HEAT_CAPACITY))
-                * HEAT_TRANSFER_SPEED
-                * time.delta_seconds();
+        assert!((total_heat(&temperatures, &materials) - initial).abs() < initial * 1e-5);
+    }
 
-        // Clamp the new temperature to the valid range and store it
-        new_temperatures[pos.x][pos.y] = new_temp.clamp(MINIMUM_HEAT, MAXIMUM_HEAT);
+    #[test]
+    fn conserves_heat_in_generated_wind() {
+        let mut temperatures = test_grid(32, 32);
+        let materials = test_materials(&temperatures);
+        let wind = wind::test_wind(*temperatures.geometry());
+        let initial = total_heat(&temperatures, &materials);
+
+        for _ in 0..10 {
+            step_in_wind(
+                &mut temperatures,
+                &materials,
+                &wind,
+                0.25,
+                ThermalBoundary::Periodic,
+            );
+        }
+
+        assert!((total_heat(&temperatures, &materials) - initial).abs() < initial * 1e-5);
     }
 
-    // Update the actual temperatures of the grid tiles with the new temperatures
-    for (pos, mut temperature) in query.iter_mut() {
-        temperature.0 = new_temperatures[pos.x][pos.y];
+    #[test]
+    fn wind_carries_heat_downwind() {
+        let geometry = *test_grid(16, 4).geometry();
+        let mut temperatures =
+            ScalarField::from_fn(geometry, |x, _| if x == 8 { 80.0 } else { 20.0 });
+        let materials = Field::new(geometry, Material::Water);
+        let wind = Field::new(geometry, Vec2::X);
+
+        step_in_wind(
+            &mut temperatures,
+            &materials,
+            &wind,
+            2.0,
+            ThermalBoundary::Periodic,
+        );
+
+        assert!(temperatures.get(10, 0) > temperatures.get(6, 0));
     }
 
-    // Clear the heat flux grid
-    heat_flux_grid.grid = vec![vec![0.0; config.grid_height]; config.grid_width];
+    #[test]
+    fn parallel_diffusion_matches_serial_diffusion() {
+        ComputeTaskPool::get_or_init(TaskPool::default);
+
+        let boundaries = ThermalBoundaries {
+            north: ThermalBoundary::FixedTemperature(10.0),
+            south: ThermalBoundary::Radiative {
+                ambient_temperature: 70.0,
+                coefficient: 0.2,
+            },
+            east: ThermalBoundary::Periodic,
+            west: ThermalBoundary::Periodic,
+        };
+        let mut serial = test_grid(40, 37);
+        let materials = test_materials(&serial);
+        let wind = swirling_wind(*serial.geometry());
+        let cooling = ScalarField::new(*materials.geometry(), 0.0);
+        let medium = Medium {
+            materials: &materials,
+            wind: &wind,
+            boundaries: &boundaries,
+            cooling: &cooling,
+        };
+        let mut parallel = serial.clone();
+        let mut buffer = serial.clone();
+
+        for _ in 0..20 {
+            integrate_heat_diffusion(&mut serial, &medium, &mut buffer, 0.5, 37);
+            integrate_heat_diffusion(&mut parallel, &medium, &mut buffer, 0.5, 3);
+        }
 
-    processed_tile_count.0 = 0;
-}
+        assert_eq!(serial.values(), parallel.values());
+    }
+
+    #[test]
+    fn long_time_steps_stay_stable() {
+        let mut temperatures = test_grid(16, 16);
+        let materials = test_materials(&temperatures);
+        let wind = swirling_wind(*temperatures.geometry());
+        let boundaries = all_edges(ThermalBoundary::Insulated);
+        let cooling = ScalarField::new(*materials.geometry(), 0.0);
+        let medium = Medium {
+            materials: &materials,
+            wind: &wind,
+            boundaries: &boundaries,
+            cooling: &cooling,
+        };
+        let elapsed = stable_time_step(&temperatures, &medium) * 20.0;
+
+        for _ in 0..10 {
+            step_in_wind(
+                &mut temperatures,
+                &materials,
+                &wind,
+                elapsed,
+                ThermalBoundary::Insulated,
+            );
+        }
 
-fn visualize_temperature(mut query: Query<(&Temperature, &mut Sprite)>) {
-    for (temp, mut sprite) in query.iter_mut() {
-        let temperature_ratio = (temp.0) / (100.0);
+        for &temperature in temperatures.values() {
+            assert!((0.0..=100.0).contains(&temperature));
+        }
+    }
+
+    #[test]
+    fn fixed_temperature_edges_pull_grid_towards_their_temperature() {
+        let mut temperatures = ScalarField::new(*test_grid(8, 8).geometry(), 20.0);
+        let materials = test_materials(&temperatures);
+
+        for _ in 0..200 {
+            step(
+                &mut temperatures,
+                &materials,
+                1.0,
+                ThermalBoundary::FixedTemperature(80.0),
+            );
+        }
+
+        for &temperature in temperatures.values() {
+            assert!((temperature - 80.0).abs() < 0.5);
+        }
+    }
+
+    #[test]
+    fn radiative_edges_lose_heat_to_a_colder_ambient() {
+        let mut temperatures = test_grid(8, 8);
+        let materials = test_materials(&temperatures);
+        let initial = total_heat(&temperatures, &materials);
+
+        step(
+            &mut temperatures,
+            &materials,
+            1.0,
+            ThermalBoundary::Radiative {
+                ambient_temperature: 0.0,
+                coefficient: 0.1,
+            },
+        );
+
+        assert!(total_heat(&temperatures, &materials) < initial);
+    }
+
+    #[test]
+    fn interfaces_conduct_at_extreme_temperatures() {
+        for temperature in [-1000.0, -200.0, 300.0, 600.0, 2000.0] {
+            for material in Material::all() {
+                assert!(material.thermal_conductivity(temperature) > 0.0);
+
+                for other in Material::all() {
+                    let conductance =
+                        interface_conductance(material, temperature + 10.0, other, temperature);
+
+                    assert!(
+                        conductance.is_finite() && conductance > 0.0,
+                        "{material:?} at {temperature} against {other:?}: {conductance}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_high_ground_colder_at_steady_state() {
+        let geometry = *test_grid(16, 16).geometry();
+        let mut temperatures = ScalarField::new(geometry, 20.0);
+        let materials = Field::new(geometry, Material::Soil);
+        let wind = swirling_wind(*temperatures.geometry());
+        let boundaries = all_edges(ThermalBoundary::Periodic);
+        // A peak in the middle, ten degrees colder at its top
+        let cooling = ScalarField::from_fn(geometry, |x, y| {
+            let distance = Vec2::new(x as f32 - 8.0, y as f32 - 8.0).length();
+            (10.0 - 2.0 * distance).max(0.0)
+        });
+        let medium = Medium {
+            materials: &materials,
+            wind: &wind,
+            boundaries: &boundaries,
+            cooling: &cooling,
+        };
+        let mut buffer = temperatures.clone();
+
+        for _ in 0..10 {
+            integrate_heat_diffusion(&mut temperatures, &medium, &mut buffer, 10.0, 16);
+        }
 
-        sprite.color = Color::srgb(temperature_ratio, 0.0, 1.0 - temperature_ratio);
+        let lowland = temperatures.get(0, 0);
+        assert!((lowland - 10.0 - temperatures.get(8, 8)).abs() < 0.1);
+        assert!((lowland - 4.0 - temperatures.get(11, 8)).abs() < 0.1);
     }
 }
@@ -0,0 +1,99 @@
+use std::f32::consts::TAU;
+
+use bevy::prelude::*;
+
+use crate::clock::SimClock;
+use crate::heat_diffusion::{HeatDiffusionSet, HeatSources, LapseCooling, TemperatureField};
+use crate::material::MaterialField;
+
+pub struct InsolationPlugin {
+    /// Heat per second reaching a cell with the sun straight overhead
+    pub solar_constant: f32,
+    /// Tilt of the planet's axis in degrees, which sets the strength of the seasons
+    pub axial_tilt: f32,
+    /// Latitude in degrees of the bottom row of the grid
+    pub south_latitude: f32,
+    /// Latitude in degrees of the top row of the grid
+    pub north_latitude: f32,
+    /// Temperature that every cell radiates heat towards
+    pub space_temperature: f32,
+    /// Heat per second radiated to space per degree above the space temperature
+    pub cooling_coefficient: f32,
+}
+
+impl Plugin for InsolationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(InsolationConfig {
+            solar_constant: self.solar_constant,
+            axial_tilt: self.axial_tilt.to_radians(),
+            south_latitude: self.south_latitude.to_radians(),
+            north_latitude: self.north_latitude.to_radians(),
+            space_temperature: self.space_temperature,
+            cooling_coefficient: self.cooling_coefficient,
+        })
+        .add_systems(FixedUpdate, insolate.in_set(HeatDiffusionSet::Sources));
+    }
+}
+
+/// Angles are stored in radians
+#[derive(Resource)]
+struct InsolationConfig {
+    solar_constant: f32,
+    axial_tilt: f32,
+    south_latitude: f32,
+    north_latitude: f32,
+    space_temperature: f32,
+    cooling_coefficient: f32,
+}
+
+impl InsolationConfig {
+    /// Latitude of the center of a row, interpolated between the south and north edges
+    fn latitude(&self, y: usize, height: usize) -> f32 {
+        let t = (y as f32 + 0.5) / height as f32;
+
+        self.south_latitude + (self.north_latitude - self.south_latitude) * t
+    }
+}
+
+/// Sine of the sun's elevation above the horizon, negative at night.
+///
+/// `hour_angle` is zero at local noon and `declination` is the latitude where
+/// the sun is overhead at noon, which moves with the seasons.
+fn solar_elevation_sine(latitude: f32, declination: f32, hour_angle: f32) -> f32 {
+    latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos()
+}
+
+/// Heats every cell with the sunlight it absorbs and cools it by radiating to space.
+///
+/// Local time moves along the x axis of the grid, so day and night sweep across it
+/// as if it wrapped around the planet. Cells radiate at their potential temperature,
+/// so high cells settle as much colder than the lowlands as their lapse cooling.
+fn insolate(
+    mut sources: ResMut<HeatSources>,
+    temperature: Res<TemperatureField>,
+    materials: Res<MaterialField>,
+    cooling: Res<LapseCooling>,
+    clock: Res<SimClock>,
+    config: Res<InsolationConfig>,
+    time: Res<Time>,
+) {
+    let declination = config.axial_tilt * (TAU * clock.time_of_year()).sin();
+    let (width, height) = (temperature.width(), temperature.height());
+
+    for y in 0..height {
+        let latitude = config.latitude(y, height);
+
+        for x in 0..width {
+            let local_time = clock.time_of_day() + x as f32 / width as f32;
+            let hour_angle = TAU * (local_time - 0.5);
+            let sunlight = solar_elevation_sine(latitude, declination, hour_angle).max(0.0);
+
+            let absorbed = config.solar_constant * sunlight * (1.0 - materials.get(x, y).albedo());
+            let radiating_temperature = temperature.get(x, y) + cooling.get(x, y);
+            let radiated =
+                config.cooling_coefficient * (radiating_temperature - config.space_temperature);
+
+            sources.add_heat(x, y, (absorbed - radiated) * time.delta_seconds());
+        }
+    }
+}
@@ -0,0 +1,296 @@
+use std::{
+    collections::BTreeMap,
+    fmt::Display,
+    io::{self, Write},
+    path::PathBuf,
+};
+
+use bevy::prelude::*;
+
+use crate::clock::SimClock;
+use crate::export;
+
+pub struct LineagePlugin {
+    /// File the ancestry tree is written to in Newick format when the app exits
+    pub newick_path: PathBuf,
+    /// File the ancestry graph is written to as JSON when the app exits
+    pub json_path: PathBuf,
+}
+
+impl Plugin for LineagePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(LineageConfig {
+            newick_path: self.newick_path.clone(),
+            json_path: self.json_path.clone(),
+        })
+        .init_resource::<Phylogeny>()
+        .observe(record_death)
+        .add_systems(Last, export_phylogeny);
+    }
+}
+
+#[derive(Resource)]
+struct LineageConfig {
+    newick_path: PathBuf,
+    json_path: PathBuf,
+}
+
+/// Identity of an organism in the ancestry graph
+#[derive(Component, Clone, Copy, Debug)]
+pub struct Lineage {
+    pub id: u64,
+    /// Parent the organism takes after. Initial organisms have none.
+    pub parent: Option<u64>,
+}
+
+struct LineageNode {
+    parent: Option<u64>,
+    /// Second parent of sexually produced organisms. It is not part of the tree,
+    /// so it may have been pruned.
+    mate: Option<u64>,
+    /// Simulated seconds since the start
+    born: f64,
+    died: Option<f64>,
+    children: Vec<u64>,
+}
+
+/// Ancestry of every living organism. Dead organisms are pruned as soon as they
+/// have no descendants left in the graph, which keeps it from growing without
+/// bound.
+#[derive(Resource, Default)]
+pub struct Phylogeny {
+    nodes: BTreeMap<u64, LineageNode>,
+    next_id: u64,
+}
+
+impl Phylogeny {
+    /// Records the birth of an organism and returns its lineage
+    pub fn birth(
+        &mut self,
+        parent: Option<&Lineage>,
+        mate: Option<&Lineage>,
+        time: f64,
+    ) -> Lineage {
+        let lineage = Lineage {
+            id: self.next_id,
+            parent: parent.map(|parent| parent.id),
+        };
+        self.next_id += 1;
+
+        if let Some(parent) = lineage
+            .parent
+            .and_then(|parent| self.nodes.get_mut(&parent))
+        {
+            parent.children.push(lineage.id);
+        }
+        self.nodes.insert(
+            lineage.id,
+            LineageNode {
+                parent: lineage.parent,
+                mate: mate.map(|mate| mate.id),
+                born: time,
+                died: None,
+                children: Vec::new(),
+            },
+        );
+
+        lineage
+    }
+
+    fn record_death(&mut self, id: u64, time: f64) {
+        if let Some(node) = self.nodes.get_mut(&id) {
+            node.died = Some(time);
+            self.prune(id);
+        }
+    }
+
+    /// Removes the node if it is dead and childless, and then its ancestors for as
+    /// long as that leaves them dead and childless too
+    fn prune(&mut self, mut id: u64) {
+        while let Some(node) = self.nodes.get(&id) {
+            if node.died.is_none() || !node.children.is_empty() {
+                return;
+            }
+
+            let parent = node.parent;
+            self.nodes.remove(&id);
+
+            let Some(parent) = parent else {
+                return;
+            };
+            if let Some(parent) = self.nodes.get_mut(&parent) {
+                parent.children.retain(|&child| child != id);
+            }
+            id = parent;
+        }
+    }
+
+    /// Writes the ancestry tree in Newick format. Nodes are labeled with their
+    /// lineage ID, and branch lengths are the simulated seconds between the births
+    /// of a parent and its child. Several initial organisms with descendants are
+    /// joined under an unlabeled root.
+    fn write_newick(&self, out: &mut impl Write) -> io::Result<()> {
+        enum Step {
+            Enter(u64),
+            Separator,
+            /// Closes the children of the node, or of the unlabeled root
+            Close(Option<u64>),
+        }
+
+        // Pushed in reverse, so that they are popped in order
+        let push_children = |stack: &mut Vec<Step>, children: &[u64]| {
+            for (index, &child) in children.iter().enumerate().rev() {
+                stack.push(Step::Enter(child));
+                if index > 0 {
+                    stack.push(Step::Separator);
+                }
+            }
+        };
+
+        let roots: Vec<u64> = self
+            .nodes
+            .iter()
+            .filter(|(_, node)| node.parent.is_none())
+            .map(|(&id, _)| id)
+            .collect();
+
+        // The tree can be deep, so it is walked with an explicit stack
+        let mut stack = Vec::new();
+        if roots.len() > 1 {
+            write!(out, "(")?;
+            stack.push(Step::Close(None));
+        }
+        push_children(&mut stack, &roots);
+
+        while let Some(step) = stack.pop() {
+            match step {
+                Step::Enter(id) => {
+                    let children = &self.nodes[&id].children;
+                    if children.is_empty() {
+                        self.write_label(out, id)?;
+                    } else {
+                        write!(out, "(")?;
+                        stack.push(Step::Close(Some(id)));
+                        push_children(&mut stack, children);
+                    }
+                }
+                Step::Separator => write!(out, ",")?,
+                Step::Close(id) => {
+                    write!(out, ")")?;
+                    if let Some(id) = id {
+                        self.write_label(out, id)?;
+                    }
+                }
+            }
+        }
+
+        writeln!(out, ";")
+    }
+
+    fn write_label(&self, out: &mut impl Write, id: u64) -> io::Result<()> {
+        let node = &self.nodes[&id];
+
+        match node.parent {
+            Some(parent) => write!(out, "{id}:{}", node.born - self.nodes[&parent].born),
+            None => write!(out, "{id}"),
+        }
+    }
+
+    /// Writes the ancestry graph as a JSON list of nodes, each referring to its
+    /// parents by ID. Living organisms have a `died` of `null`.
+    fn write_json(&self, out: &mut impl Write) -> io::Result<()> {
+        fn or_null(value: Option<impl Display>) -> String {
+            value.map_or("null".to_string(), |value| value.to_string())
+        }
+
+        writeln!(out, "{{\"nodes\": [")?;
+        for (index, (id, node)) in self.nodes.iter().enumerate() {
+            let separator = if index + 1 < self.nodes.len() {
+                ","
+            } else {
+                ""
+            };
+
+            writeln!(
+                out,
+                "  {{\"id\": {id}, \"parent\": {}, \"mate\": {}, \"born\": {}, \"died\": {}}}{separator}",
+                or_null(node.parent),
+                or_null(node.mate),
+                node.born,
+                or_null(node.died),
+            )?;
+        }
+        writeln!(out, "]}}")
+    }
+}
+
+/// Marks organisms as dead in the phylogeny as they are despawned, whatever
+/// killed them
+fn record_death(
+    trigger: Trigger<OnRemove, Lineage>,
+    lineages: Query<&Lineage>,
+    mut phylogeny: ResMut<Phylogeny>,
+    clock: Res<SimClock>,
+) {
+    if let Ok(lineage) = lineages.get(trigger.entity()) {
+        phylogeny.record_death(lineage.id, clock.elapsed());
+    }
+}
+
+fn export_phylogeny(
+    mut exits: EventReader<AppExit>,
+    phylogeny: Res<Phylogeny>,
+    config: Res<LineageConfig>,
+) {
+    if exits.read().next().is_none() {
+        return;
+    }
+
+    export::write_file(&config.newick_path, "phylogeny", |out| {
+        phylogeny.write_newick(out)
+    });
+    export::write_file(&config.json_path, "phylogeny", |out| {
+        phylogeny.write_json(out)
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn newick(phylogeny: &Phylogeny) -> String {
+        let mut out = Vec::new();
+        phylogeny.write_newick(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn writes_every_initial_organism_under_one_root() {
+        let mut phylogeny = Phylogeny::default();
+        let first = phylogeny.birth(None, None, 0.0);
+        let second = phylogeny.birth(None, None, 0.0);
+        let child = phylogeny.birth(Some(&first), None, 1.5);
+        phylogeny.birth(Some(&child), Some(&second), 4.0);
+
+        assert_eq!(newick(&phylogeny), "(((3:2.5)2:1.5)0,1);\n");
+    }
+
+    #[test]
+    fn prunes_dead_branches_without_descendants() {
+        let mut phylogeny = Phylogeny::default();
+        let root = phylogeny.birth(None, None, 0.0);
+        let survivor = phylogeny.birth(Some(&root), None, 1.0);
+        let dead_end = phylogeny.birth(Some(&root), None, 2.0);
+        let grandchild = phylogeny.birth(Some(&dead_end), None, 3.0);
+
+        phylogeny.record_death(root.id, 4.0);
+        phylogeny.record_death(dead_end.id, 5.0);
+        assert_eq!(phylogeny.nodes.len(), 4);
+
+        phylogeny.record_death(grandchild.id, 6.0);
+        assert_eq!(newick(&phylogeny), "(1:1)0;\n");
+
+        phylogeny.record_death(survivor.id, 7.0);
+        assert!(phylogeny.nodes.is_empty());
+    }
+}
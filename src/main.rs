@@ -1,10 +1,37 @@
-use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+// Bevy systems routinely exceed clippy's type complexity and argument count thresholds
+#![allow(clippy::type_complexity, clippy::too_many_arguments)]
 
+use bevy::prelude::*;
+use heat_diffusion::{ThermalBoundaries, ThermalBoundary};
+
+mod boundary;
+mod brain;
 mod camera;
+mod clock;
+mod diffusion;
+mod export;
+mod field;
+mod genome;
 mod heat_diffusion;
+mod insolation;
+mod lineage;
+mod material;
+mod mating;
+mod moisture;
+mod neat;
+mod organism;
+mod overlay;
+mod pheromone;
+mod predation;
+mod spatial;
+mod species;
+mod steering;
 mod stepping;
+mod terrain;
+mod thermotaxis;
+mod vegetation;
+mod wind;
 
-const ORGANISM_COLOR: Color = Color::srgba(0.2, 0.8, 0.5, 0.6);
 const GRID_WIDTH: usize = 64;
 const GRID_HEIGHT: usize = 64;
 const CELL_SIZE: f32 = 32.0;
@@ -29,76 +56,95 @@ fn main() {
             grid_height: GRID_HEIGHT,
             cell_size: CELL_SIZE,
             world_size: WORLD_SIZE,
+            // A cold pole in the north and a warm equator in the south. Organisms bounce
+            // off the edges, so no edge is periodic.
+            boundaries: ThermalBoundaries {
+                north: ThermalBoundary::FixedTemperature(10.0),
+                south: ThermalBoundary::Radiative {
+                    ambient_temperature: 70.0,
+                    coefficient: 0.2,
+                },
+                east: ThermalBoundary::Insulated,
+                west: ThermalBoundary::Insulated,
+            },
+        })
+        .add_plugins(terrain::TerrainPlugin {
+            seed: rand::random(),
+            octaves: 5,
+            frequency: 0.04,
+            persistence: 0.5,
+            max_elevation: 2000.0,
+            sea_level: -300.0,
+            lapse_rate: 0.01,
+            max_slope: 3.0,
+        })
+        .add_plugins(wind::WindPlugin {
+            seed: rand::random(),
+            prevailing: Vec2::new(8.0, 0.0),
+            turbulence: 20.0,
+            frequency: 0.08,
+            drag: 0.2,
+        })
+        .add_plugins(clock::ClockPlugin {
+            day_length: 60.0,
+            days_per_year: 12.0,
+        })
+        .add_plugins(insolation::InsolationPlugin {
+            solar_constant: 1.0,
+            axial_tilt: 23.44,
+            // The south edge is on the equator
+            south_latitude: 0.0,
+            north_latitude: 70.0,
+            space_temperature: -10.0,
+            cooling_coefficient: 0.004,
+        })
+        .add_plugins(organism::OrganismPlugin {
+            world_size: WORLD_SIZE,
+            initial_count: 5000,
+            carnivore_fraction: 0.05,
+            brain_fraction: 0.2,
+            reproduction: organism::Reproduction::Sexual,
+        })
+        .add_plugins(boundary::BoundaryPlugin {
+            mode: boundary::BoundaryMode::Bounce,
+        })
+        .add_plugins(thermotaxis::ThermotaxisPlugin {
+            sensing_radius: CELL_SIZE,
+            strength: 1.0,
+            turn_rate: 1.5,
+        })
+        .add_plugins(spatial::SpatialIndexPlugin)
+        .add_plugins(steering::SteeringPlugin {
+            flock_size: 6,
+            flock_radius: 48.0,
+            slowing_radius: 32.0,
+            wander_jitter: 2.0,
+        })
+        .add_plugins(overlay::OverlayPlugin)
+        .add_plugins(vegetation::VegetationPlugin)
+        .add_plugins(moisture::MoisturePlugin)
+        .add_plugins(pheromone::PheromonePlugin {
+            sensing_radius: CELL_SIZE,
+            strength: 1.0,
+        })
+        .add_plugins(predation::PredationPlugin {
+            detection_radius: 96.0,
+            prey_size_ratio: 0.8,
+            digestion_efficiency: 0.6,
+        })
+        .add_plugins(brain::BrainPlugin)
+        .add_plugins(mating::MatingPlugin {
+            search_radius: 96.0,
+        })
+        .add_plugins(species::SpeciesPlugin {
+            interval: 5.0,
+            log_path: "species_log.csv".into(),
+        })
+        .add_plugins(lineage::LineagePlugin {
+            newick_path: "phylogeny.nwk".into(),
+            json_path: "phylogeny.json".into(),
         })
         .insert_resource(ClearColor(Color::srgb(0.8, 0.78, 0.88)))
-        .add_systems(Startup, setup)
-        .add_systems(
-            FixedUpdate,
-            (apply_velocity)
-                // `chain`ing systems together runs them in order
-                .chain(),
-        )
         .add_systems(Update, (bevy::window::close_when_requested))
         .run();
 }
-
-#[derive(Component)]
-struct Organism;
-
-#[derive(Component, Deref, DerefMut)]
-struct Velocity(Vec2);
-
-fn setup(
-    mut commands: Commands,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<ColorMaterial>>,
-) {
-    let organism_mesh_handle = meshes.add(Circle::default());
-
-    (0..5000).for_each(|_| {
-        let position = Vec3::new(
-            rand::random::<f32>() * WORLD_SIZE.x - WORLD_SIZE.x / 2.0,
-            rand::random::<f32>() * WORLD_SIZE.y - WORLD_SIZE.y / 2.0,
-            1.0,
-        );
-
-        let scale = Vec3::new(
-            rand::random::<f32>() * 4.0 + 4.0,
-            rand::random::<f32>() * 4.0 + 4.0,
-            1.0,
-        );
-
-        let velocity = Vec2::new(
-            rand::random::<f32>() * 16.0 - 8.0,
-            rand::random::<f32>() * 16.0 - 8.0,
-        );
-
-        let linear_color: LinearRgba = ORGANISM_COLOR.into();
-
-        let color_variation = Color::srgba(
-            (linear_color.red + rand::random::<f32>() * 0.2 - 0.1).clamp(0.0, 1.0),
-            (linear_color.green + rand::random::<f32>() * 0.2 - 0.1).clamp(0.0, 1.0),
-            (linear_color.blue + rand::random::<f32>() * 0.2 - 0.1).clamp(0.0, 1.0),
-            linear_color.alpha,
-        );
-        let organism_material_handle = materials.add(color_variation);
-
-        commands.spawn((
-            MaterialMesh2dBundle {
-                mesh: organism_mesh_handle.clone().into(),
-                material: organism_material_handle.clone(),
-                transform: Transform::from_translation(position).with_scale(scale),
-                ..default()
-            },
-            Organism,
-            Velocity(velocity),
-        ));
-    });
-}
-
-fn apply_velocity(mut query: Query<(&mut Transform, &Velocity)>, time: Res<Time>) {
-    for (mut transform, velocity) in &mut query {
-        transform.translation.x += velocity.x * time.delta_seconds();
-        transform.translation.y += velocity.y * time.delta_seconds();
-    }
-}
@@ -0,0 +1,82 @@
+use bevy::prelude::*;
+
+use crate::field::Field;
+
+const TILE_VOLUME: f32 = 0.5;
+// Floor for the linear conductivity fits, which reach zero at extreme temperatures
+const MIN_THERMAL_CONDUCTIVITY: f32 = 0.01;
+
+/// What the ground of a grid cell is made of
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Material {
+    Water,
+    Ice,
+    Sand,
+    Soil,
+    Rock,
+}
+
+impl Material {
+    /// How well heat conducts through the material at the given temperature
+    pub fn thermal_conductivity(&self, temperature: f32) -> f32 {
+        let conductivity = match self {
+            Material::Water => 0.6065 - 0.00122 * temperature + 0.0000063 * temperature.powi(2),
+            Material::Ice => 2.2 - 0.01 * temperature,
+            Material::Sand => 0.3 + 0.002 * temperature,
+            Material::Soil => 0.8 + 0.001 * temperature,
+            Material::Rock => 2.5 - 0.005 * temperature,
+        };
+
+        conductivity.max(MIN_THERMAL_CONDUCTIVITY)
+    }
+
+    pub fn density(&self) -> f32 {
+        match self {
+            Material::Water => 1.0,
+            Material::Ice => 0.92,
+            Material::Sand => 1.6,
+            Material::Soil => 1.3,
+            Material::Rock => 2.7,
+        }
+    }
+
+    pub fn specific_heat_capacity(&self) -> f32 {
+        match self {
+            Material::Water => 4.18,
+            Material::Ice => 2.1,
+            Material::Sand => 0.8,
+            Material::Soil => 1.5,
+            Material::Rock => 0.8,
+        }
+    }
+
+    /// Fraction of sunlight reflected instead of absorbed
+    pub fn albedo(&self) -> f32 {
+        match self {
+            Material::Water => 0.06,
+            Material::Ice => 0.6,
+            Material::Sand => 0.35,
+            Material::Soil => 0.17,
+            Material::Rock => 0.2,
+        }
+    }
+
+    /// Heat needed to warm a whole tile of this material by one degree
+    pub fn tile_heat_capacity(&self) -> f32 {
+        self.density() * TILE_VOLUME * self.specific_heat_capacity()
+    }
+
+    pub fn all() -> [Material; 5] {
+        [
+            Material::Water,
+            Material::Ice,
+            Material::Sand,
+            Material::Soil,
+            Material::Rock,
+        ]
+    }
+}
+
+/// Material of every grid cell
+#[derive(Resource, Deref, DerefMut)]
+pub struct MaterialField(pub Field<Material>);
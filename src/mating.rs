@@ -0,0 +1,230 @@
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::boundary::{self, BoundaryMode};
+use crate::brain::{self, Brain};
+use crate::clock::SimClock;
+use crate::field::GridGeometry;
+use crate::genome::Genome;
+use crate::lineage::{Lineage, Phylogeny};
+use crate::neat::Innovations;
+use crate::organism::{
+    self, Energy, Organism, OrganismAssets, OrganismSet, Reproduction, Velocity,
+};
+use crate::spatial::SpatialIndex;
+use crate::species::SpeciesId;
+use crate::steering::{self, SteeringForce};
+
+const PARENTAL_INVESTMENT: f32 = 0.25; // Fraction of each parent's energy given to their offspring
+const MATING_COST: f32 = 5.0; // Energy lost by each parent on top of its investment
+
+/// Sexual reproduction: organisms ready to reproduce look for a genetically
+/// compatible mate that is ready too, and when they touch they have an
+/// offspring whose genome is a mutated crossover of both of theirs
+pub struct MatingPlugin {
+    /// How far organisms can see potential mates
+    pub search_radius: f32,
+}
+
+impl Plugin for MatingPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(MatingConfig {
+            search_radius: self.search_radius,
+        })
+        .add_systems(
+            FixedUpdate,
+            (
+                seek_mates.in_set(OrganismSet::Steer),
+                mate.in_set(OrganismSet::Lifecycle),
+            )
+                .run_if(resource_equals(Reproduction::Sexual)),
+        );
+    }
+}
+
+#[derive(Resource)]
+pub struct MatingConfig {
+    search_radius: f32,
+}
+
+impl MatingConfig {
+    /// Position of the nearest organism `entity` can see that is ready to mate
+    /// and genetically compatible with it
+    pub fn nearest_mate(
+        &self,
+        entity: Entity,
+        position: Vec2,
+        genome: &Genome,
+        candidates: &Query<(&Genome, &Energy), With<Organism>>,
+        index: &SpatialIndex,
+    ) -> Option<Vec2> {
+        index
+            .within_radius(position, self.search_radius)
+            .filter(|(other, _)| *other != entity)
+            .filter(|(other, _)| {
+                candidates
+                    .get(*other)
+                    .is_ok_and(|(other_genome, other_energy)| {
+                        is_ready(other_genome, other_energy) && genome.is_compatible(other_genome)
+                    })
+            })
+            .map(|(_, other_position)| other_position)
+            .min_by(|a, b| {
+                a.distance_squared(position)
+                    .total_cmp(&b.distance_squared(position))
+            })
+    }
+
+    pub fn search_radius(&self) -> f32 {
+        self.search_radius
+    }
+}
+
+/// Whether an organism has built up enough energy to reproduce
+pub fn is_ready(genome: &Genome, energy: &Energy) -> bool {
+    energy.0 >= genome.reproduction_threshold
+}
+
+/// Steers organisms that are ready to reproduce towards the nearest compatible
+/// mate they can see
+fn seek_mates(
+    mut organisms: Query<
+        (
+            Entity,
+            &Transform,
+            &Genome,
+            &Energy,
+            &Velocity,
+            &mut SteeringForce,
+        ),
+        (With<Organism>, Without<Brain>),
+    >,
+    candidates: Query<(&Genome, &Energy), With<Organism>>,
+    index: Res<SpatialIndex>,
+    config: Res<MatingConfig>,
+) {
+    for (entity, transform, genome, energy, velocity, mut force) in organisms.iter_mut() {
+        if !is_ready(genome, energy) {
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+        let Some(mate) = config.nearest_mate(entity, position, genome, &candidates, &index) else {
+            continue;
+        };
+
+        force.0 +=
+            steering::seek(position, velocity.0, mate, genome.max_speed) * genome.steering.seek;
+    }
+}
+
+/// Pairs up touching organisms that are ready and compatible, and spawns an
+/// offspring between each pair. The offspring's brain and lineage take after the
+/// parent with more energy.
+fn mate(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut query: Query<
+        (
+            Entity,
+            &Transform,
+            &Genome,
+            &Lineage,
+            &mut Energy,
+            Option<&Brain>,
+            Option<&SpeciesId>,
+        ),
+        With<Organism>,
+    >,
+    mut innovations: ResMut<Innovations>,
+    mut phylogeny: ResMut<Phylogeny>,
+    index: Res<SpatialIndex>,
+    assets: Res<OrganismAssets>,
+    clock: Res<SimClock>,
+    geometry: Res<GridGeometry>,
+    mode: Option<Res<BoundaryMode>>,
+) {
+    let willing = |genome: &Genome, energy: &Energy, brain: Option<&Brain>| {
+        is_ready(genome, energy) && brain::wants_to_reproduce(brain)
+    };
+
+    let mut paired = HashSet::new();
+    let mut couples = Vec::new();
+
+    for (entity, transform, genome, _, energy, brain, _) in query.iter() {
+        if paired.contains(&entity) || !willing(genome, energy, brain) {
+            continue;
+        }
+
+        let position = transform.translation.truncate();
+
+        // Larger mates can reach further, and find this organism when it is their turn
+        let max_reach = genome.size;
+
+        let mate = index
+            .within_radius(position, max_reach)
+            .find(|(other, other_position)| {
+                let Ok((_, _, other_genome, _, other_energy, other_brain, _)) = query.get(*other)
+                else {
+                    return false;
+                };
+                // Organisms are circles whose diameter is their size
+                let reach = (genome.size + other_genome.size) / 2.0;
+
+                *other != entity
+                    && !paired.contains(other)
+                    && willing(other_genome, other_energy, other_brain)
+                    && genome.is_compatible(other_genome)
+                    && other_position.distance(position) <= reach
+            });
+
+        // The mate's position is as seen from this organism, so the offspring is born
+        // between them even when they touch across a wrapping edge
+        if let Some((mate, mate_position)) = mate {
+            paired.insert(entity);
+            paired.insert(mate);
+            couples.push((entity, mate, (position + mate_position) / 2.0));
+        }
+    }
+
+    let wrap = boundary::world_wrap(mode.as_deref());
+
+    for (first, second, midpoint) in couples {
+        let Ok([first, second]) = query.get_many_mut([first, second]) else {
+            continue;
+        };
+        let (_, _, first_genome, first_lineage, mut first_energy, _, first_species) = first;
+        let (_, _, second_genome, second_lineage, mut second_energy, _, second_species) = second;
+
+        let (genome, lineage, species) = if first_energy.0 >= second_energy.0 {
+            (
+                Genome::crossover(first_genome, second_genome),
+                phylogeny.birth(Some(first_lineage), Some(second_lineage), clock.elapsed()),
+                first_species.copied(),
+            )
+        } else {
+            (
+                Genome::crossover(second_genome, first_genome),
+                phylogeny.birth(Some(second_lineage), Some(first_lineage), clock.elapsed()),
+                second_species.copied(),
+            )
+        };
+
+        let mut offspring_energy = 0.0;
+        for energy in [&mut first_energy, &mut second_energy] {
+            let investment = energy.0 * PARENTAL_INVESTMENT;
+            energy.0 -= investment + MATING_COST;
+            offspring_energy += investment;
+        }
+
+        organism::spawn_organism(
+            &mut commands,
+            &assets,
+            &mut materials,
+            genome.mutate(&mut innovations),
+            geometry.wrap_position(midpoint, wrap),
+            offspring_energy,
+            lineage,
+            species,
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use bevy::prelude::*;
+
+use crate::diffusion::{Concentration, DiffusingField, DiffusionPlugin, DiffusionSet, Sources};
+use crate::field::{Field, GridGeometry, ScalarField};
+use crate::heat_diffusion::{HeatDiffusionSet, TemperatureField};
+use crate::material::{Material, MaterialField};
+use crate::overlay::{self, Overlay, OverlayImage};
+use crate::wind::WindField;
+
+const SATURATION_HUMIDITY: f32 = 10.0; // Most water the air of a cell can hold at a temperature of 0
+const SATURATION_GROWTH: f32 = 0.03; // Relative increase of the saturation humidity per degree
+const EVAPORATION_RATE: f32 = 0.05; // Fraction of the air's saturation deficit evaporated per second from open water
+const PRECIPITATION_RATE: f32 = 0.2; // Fraction of the humidity above saturation that rains out per second
+const SOIL_CAPACITY: f32 = 100.0; // Most water the ground of a cell can hold
+const INITIAL_SOIL_MOISTURE: f32 = 30.0;
+const SOIL_DRAINAGE: f32 = 0.002; // Fraction of the soil moisture lost to runoff per second
+const TUNDRA_TEMPERATURE: f32 = 15.0; // Land colder than this is tundra
+const DESERT_MOISTURE: f32 = 0.15; // Land with a lower fraction of its soil capacity filled is desert
+const FOREST_MOISTURE: f32 = 0.5; // Land with a higher fraction of its soil capacity filled is forest
+
+pub struct MoisturePlugin;
+
+impl Plugin for MoisturePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins(DiffusionPlugin::<Humidity>::default())
+            .add_systems(Startup, setup)
+            .add_systems(
+                FixedUpdate,
+                (
+                    exchange_water
+                        .in_set(DiffusionSet::Sources)
+                        .after(HeatDiffusionSet::Diffuse),
+                    classify_biomes.after(DiffusionSet::Step),
+                ),
+            )
+            .add_systems(
+                Update,
+                visualize_biomes.run_if(resource_equals(Overlay::Biome)),
+            );
+    }
+}
+
+/// Water vapor in the air above every grid cell, spread and blown around like heat
+pub struct Humidity;
+
+impl DiffusingField for Humidity {
+    const NAME: &'static str = "humidity";
+    const DIFFUSIVITY: f32 = 0.2;
+    const INITIAL: f32 = 20.0;
+    const OVERLAY_KEY: KeyCode = KeyCode::Digit3;
+
+    fn color(humidity: f32) -> Color {
+        let ratio = (humidity / 80.0).clamp(0.0, 1.0); // Fully blue at a humidity of 80
+
+        Color::srgb(1.0 - ratio, 1.0 - ratio * 0.6, 1.0)
+    }
+
+    fn flow(wind: &WindField) -> Option<&Field<Vec2>> {
+        Some(wind)
+    }
+}
+
+/// Water held in the ground of every grid cell
+#[derive(Resource, Deref, DerefMut)]
+pub struct SoilMoistureField(pub ScalarField);
+
+/// Kind of landscape a grid cell's climate supports
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Biome {
+    Ocean,
+    Tundra,
+    Desert,
+    Grassland,
+    Forest,
+}
+
+impl Biome {
+    fn classify(material: Material, temperature: f32, soil_moisture: f32) -> Biome {
+        let wetness = soil_moisture / SOIL_CAPACITY;
+
+        match material {
+            Material::Water => Biome::Ocean,
+            Material::Ice => Biome::Tundra,
+            _ if temperature < TUNDRA_TEMPERATURE => Biome::Tundra,
+            _ if wetness < DESERT_MOISTURE => Biome::Desert,
+            _ if wetness < FOREST_MOISTURE => Biome::Grassland,
+            _ => Biome::Forest,
+        }
+    }
+
+    fn color(&self) -> Color {
+        match self {
+            Biome::Ocean => Color::srgb(0.1, 0.2, 0.5),
+            Biome::Tundra => Color::srgb(0.8, 0.85, 0.9),
+            Biome::Desert => Color::srgb(0.9, 0.8, 0.5),
+            Biome::Grassland => Color::srgb(0.6, 0.8, 0.3),
+            Biome::Forest => Color::srgb(0.1, 0.45, 0.15),
+        }
+    }
+}
+
+/// Biome of every grid cell
+#[derive(Resource, Deref, DerefMut)]
+pub struct BiomeField(pub Field<Biome>);
+
+/// Most water the air can hold before it rains, growing exponentially with the
+/// temperature like real saturation vapor pressure
+fn saturation_humidity(temperature: f32) -> f32 {
+    SATURATION_HUMIDITY * (SATURATION_GROWTH * temperature).exp()
+}
+
+fn setup(mut commands: Commands, geometry: Res<GridGeometry>) {
+    commands.insert_resource(SoilMoistureField(ScalarField::new(
+        *geometry,
+        INITIAL_SOIL_MOISTURE,
+    )));
+    commands.insert_resource(BiomeField(Field::new(*geometry, Biome::Grassland)));
+}
+
+/// Evaporates water into air below saturation, faster the hotter it is, and rains
+/// the excess out of air above saturation, which happens as it is cooled. Land can
+/// only evaporate the water in its soil, and slowly loses some of it to runoff.
+fn exchange_water(
+    mut humidity_sources: ResMut<Sources<Humidity>>,
+    humidity: Res<Concentration<Humidity>>,
+    mut soil_moisture: ResMut<SoilMoistureField>,
+    temperature: Res<TemperatureField>,
+    materials: Res<MaterialField>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+
+    for ((((humidity_source, &humidity), soil_moisture), &temperature), material) in
+        humidity_sources
+            .values_mut()
+            .iter_mut()
+            .zip(humidity.values())
+            .zip(soil_moisture.values_mut())
+            .zip(temperature.values())
+            .zip(materials.values())
+    {
+        let saturation = saturation_humidity(temperature);
+        let availability = match material {
+            Material::Water => 1.0,
+            Material::Ice => 0.0,
+            _ => *soil_moisture / SOIL_CAPACITY,
+        };
+
+        let evaporation = EVAPORATION_RATE * (saturation - humidity).max(0.0) * availability * dt;
+        let precipitation = PRECIPITATION_RATE * (humidity - saturation).max(0.0) * dt;
+
+        *humidity_source += evaporation - precipitation;
+
+        // Open water never runs dry and rain falling on it joins the sea
+        if *material != Material::Water {
+            *soil_moisture = (*soil_moisture + precipitation - evaporation).min(SOIL_CAPACITY);
+            *soil_moisture -= *soil_moisture * SOIL_DRAINAGE * dt;
+        }
+    }
+}
+
+fn classify_biomes(
+    mut biomes: ResMut<BiomeField>,
+    soil_moisture: Res<SoilMoistureField>,
+    temperature: Res<TemperatureField>,
+    materials: Res<MaterialField>,
+) {
+    for (((biome, &soil_moisture), &temperature), &material) in biomes
+        .values_mut()
+        .iter_mut()
+        .zip(soil_moisture.values())
+        .zip(temperature.values())
+        .zip(materials.values())
+    {
+        *biome = Biome::classify(material, temperature, soil_moisture);
+    }
+}
+
+fn visualize_biomes(
+    biomes: Res<BiomeField>,
+    overlay_image: Res<OverlayImage>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    overlay::paint(&mut images, &overlay_image, &biomes, |biome| biome.color());
+}
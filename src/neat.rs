@@ -0,0 +1,410 @@
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use rand::{seq::SliceRandom, Rng};
+
+use crate::genome;
+
+pub const INPUTS: usize = 11; // Bias, temperature, temperature gradient, food, threat, mate and energy
+pub const OUTPUTS: usize = 4; // Desired velocity, eat and reproduce
+const WEIGHT_RANGE: (f32, f32) = (-4.0, 4.0);
+const INITIAL_WEIGHT: f32 = 1.0; // Initial weights are drawn from [-INITIAL_WEIGHT, INITIAL_WEIGHT]
+const ADD_CONNECTION_RATE: f32 = 0.05; // Chance for an offspring's network to gain a connection
+const ADD_NODE_RATE: f32 = 0.03; // Chance for an offspring's network to gain a hidden node
+const DISABLED_INHERITANCE: f32 = 0.75; // Chance for a connection disabled in either parent to stay disabled
+const DISJOINT_COEFFICIENT: f32 = 1.0; // Compatibility distance per connection only one network has
+const WEIGHT_COEFFICIENT: f32 = 0.5; // Compatibility distance per unit of mean weight difference
+const SMALL_NETWORK: usize = 20; // Disjoint connections of smaller networks are not normalized by their size
+
+/// Historical markings of the structural mutations that have happened so far.
+///
+/// Every connection between the same two nodes gets the same innovation number,
+/// and every split of the same connection the same new node, no matter in which
+/// lineage they appear, so that networks can be lined up for crossover.
+#[derive(Resource)]
+pub struct Innovations {
+    connections: HashMap<(usize, usize), usize>,
+    splits: HashMap<usize, usize>,
+    next_innovation: usize,
+    next_node: usize,
+}
+
+impl Default for Innovations {
+    fn default() -> Self {
+        Innovations {
+            connections: initial_connections()
+                .map(|(innovation, from, to)| ((from, to), innovation))
+                .collect(),
+            splits: HashMap::new(),
+            next_innovation: INPUTS * OUTPUTS,
+            next_node: INPUTS + OUTPUTS,
+        }
+    }
+}
+
+impl Innovations {
+    fn connection(&mut self, from: usize, to: usize) -> usize {
+        *self.connections.entry((from, to)).or_insert_with(|| {
+            self.next_innovation += 1;
+            self.next_innovation - 1
+        })
+    }
+
+    /// Hidden node placed on the connection with the given innovation number
+    fn split(&mut self, innovation: usize) -> usize {
+        *self.splits.entry(innovation).or_insert_with(|| {
+            self.next_node += 1;
+            self.next_node - 1
+        })
+    }
+}
+
+/// Innovation number, source and target of every input to output connection,
+/// which all networks start with
+fn initial_connections() -> impl Iterator<Item = (usize, usize, usize)> {
+    (0..INPUTS).flat_map(|input| {
+        (0..OUTPUTS).map(move |output| (input * OUTPUTS + output, input, INPUTS + output))
+    })
+}
+
+#[derive(Clone, Debug)]
+struct Connection {
+    innovation: usize,
+    from: usize,
+    to: usize,
+    weight: f32,
+    enabled: bool,
+}
+
+/// Heritable NEAT genome of a feed-forward network whose hidden nodes and
+/// connections are added by mutation.
+///
+/// Nodes `0..INPUTS` are the inputs and the next `OUTPUTS` nodes the outputs.
+#[derive(Clone, Debug)]
+pub struct NeuralNetwork {
+    /// Sorted by innovation number
+    connections: Vec<Connection>,
+    /// Hidden and output nodes, each after all the nodes feeding into it
+    order: Vec<usize>,
+}
+
+impl NeuralNetwork {
+    /// Network connecting every input directly to every output, with random weights
+    pub fn random() -> Self {
+        let mut rng = rand::thread_rng();
+
+        let mut network = NeuralNetwork {
+            connections: initial_connections()
+                .map(|(innovation, from, to)| Connection {
+                    innovation,
+                    from,
+                    to,
+                    weight: rng.gen_range(-INITIAL_WEIGHT..INITIAL_WEIGHT),
+                    enabled: true,
+                })
+                .collect(),
+            order: Vec::new(),
+        };
+        network.sort_nodes();
+        network
+    }
+
+    /// Returns a copy of this network with its weights perturbed, and occasionally
+    /// a new connection or hidden node
+    pub fn mutate(&self, innovations: &mut Innovations) -> Self {
+        let mut rng = rand::thread_rng();
+        let mut network = self.clone();
+
+        for connection in network.connections.iter_mut() {
+            connection.weight = genome::mutate_gene(connection.weight, WEIGHT_RANGE);
+        }
+        if rng.gen::<f32>() < ADD_CONNECTION_RATE {
+            network.add_connection(innovations);
+        }
+        if rng.gen::<f32>() < ADD_NODE_RATE {
+            network.add_node(innovations);
+        }
+
+        network.sort_nodes();
+        network
+    }
+
+    /// Offspring with the structure of the fitter parent, taking the weight of
+    /// every connection both parents have from either of them at random
+    pub fn crossover(fitter: &NeuralNetwork, other: &NeuralNetwork) -> Self {
+        let mut rng = rand::thread_rng();
+
+        let connections = fitter
+            .connections
+            .iter()
+            .map(|connection| {
+                let Some(matching) = other.connection(connection.innovation) else {
+                    return connection.clone();
+                };
+
+                let mut inherited = if rng.gen() {
+                    connection.clone()
+                } else {
+                    matching.clone()
+                };
+                inherited.enabled = (connection.enabled && matching.enabled)
+                    || rng.gen::<f32>() >= DISABLED_INHERITANCE;
+                inherited
+            })
+            .collect();
+
+        // Only weights and enabled connections differ from the fitter parent, so
+        // its nodes are still in order
+        NeuralNetwork {
+            connections,
+            order: fitter.order.clone(),
+        }
+    }
+
+    /// How different the structure and weights of two networks are, NEAT's
+    /// compatibility distance. It is what speciates organisms with brains: it is
+    /// part of `Genome::distance`, by which the `SpeciesRegistry` clusters organisms
+    /// into species and mates are judged compatible.
+    pub fn compatibility_distance(&self, other: &NeuralNetwork) -> f32 {
+        let mut matching = 0;
+        let mut weight_difference = 0.0;
+
+        for connection in &self.connections {
+            if let Some(other_connection) = other.connection(connection.innovation) {
+                matching += 1;
+                weight_difference += (connection.weight - other_connection.weight).abs();
+            }
+        }
+
+        let disjoint = self.connections.len() + other.connections.len() - 2 * matching;
+        let size = self.connections.len().max(other.connections.len());
+        let normalization = if size < SMALL_NETWORK {
+            1.0
+        } else {
+            size as f32
+        };
+
+        DISJOINT_COEFFICIENT * disjoint as f32 / normalization
+            + WEIGHT_COEFFICIENT * weight_difference / matching.max(1) as f32
+    }
+
+    /// Outputs in [-1, 1] for the given inputs
+    pub fn evaluate(&self, inputs: &[f32; INPUTS]) -> [f32; OUTPUTS] {
+        let mut values: HashMap<usize, f32> = inputs.iter().copied().enumerate().collect();
+
+        for &node in &self.order {
+            let sum: f32 = self
+                .connections
+                .iter()
+                .filter(|connection| connection.enabled && connection.to == node)
+                .map(|connection| connection.weight * values.get(&connection.from).unwrap_or(&0.0))
+                .sum();
+
+            values.insert(node, sum.tanh());
+        }
+
+        std::array::from_fn(|output| values[&(INPUTS + output)])
+    }
+
+    fn connection(&self, innovation: usize) -> Option<&Connection> {
+        self.connections
+            .binary_search_by_key(&innovation, |connection| connection.innovation)
+            .ok()
+            .map(|index| &self.connections[index])
+    }
+
+    fn insert(&mut self, connection: Connection) {
+        let index = self
+            .connections
+            .partition_point(|other| other.innovation < connection.innovation);
+        self.connections.insert(index, connection);
+    }
+
+    fn hidden_nodes(&self) -> impl Iterator<Item = usize> + '_ {
+        self.order
+            .iter()
+            .copied()
+            .filter(|&node| node >= INPUTS + OUTPUTS)
+    }
+
+    /// Whether `target` can be reached from `node` by following connections
+    fn reaches(&self, node: usize, target: usize) -> bool {
+        let mut visited = HashSet::new();
+        let mut stack = vec![node];
+
+        while let Some(node) = stack.pop() {
+            if node == target {
+                return true;
+            }
+            if visited.insert(node) {
+                stack.extend(
+                    self.connections
+                        .iter()
+                        .filter(|connection| connection.from == node)
+                        .map(|connection| connection.to),
+                );
+            }
+        }
+
+        false
+    }
+
+    /// Connects two random nodes that are not connected yet, unless that would
+    /// create a cycle
+    fn add_connection(&mut self, innovations: &mut Innovations) {
+        let mut rng = rand::thread_rng();
+
+        let sources: Vec<usize> = (0..INPUTS).chain(self.hidden_nodes()).collect();
+        let targets: Vec<usize> = self
+            .hidden_nodes()
+            .chain(INPUTS..INPUTS + OUTPUTS)
+            .collect();
+        let (Some(&from), Some(&to)) = (sources.choose(&mut rng), targets.choose(&mut rng)) else {
+            return;
+        };
+
+        let exists = self
+            .connections
+            .iter()
+            .any(|connection| connection.from == from && connection.to == to);
+        if exists || self.reaches(to, from) {
+            return;
+        }
+
+        self.insert(Connection {
+            innovation: innovations.connection(from, to),
+            from,
+            to,
+            weight: rng.gen_range(-INITIAL_WEIGHT..INITIAL_WEIGHT),
+            enabled: true,
+        });
+    }
+
+    /// Splits a random enabled connection in two with a new hidden node. The
+    /// connection into the node has a weight of 1 and the one out of it the old
+    /// weight, so that the network behaves about the same as before.
+    fn add_node(&mut self, innovations: &mut Innovations) {
+        let mut rng = rand::thread_rng();
+
+        let enabled: Vec<usize> = (0..self.connections.len())
+            .filter(|&index| self.connections[index].enabled)
+            .collect();
+        let Some(&index) = enabled.choose(&mut rng) else {
+            return;
+        };
+
+        let Connection {
+            innovation,
+            from,
+            to,
+            weight,
+            ..
+        } = self.connections[index];
+        let node = innovations.split(innovation);
+
+        // The connection has been split before in this lineage and later re-enabled
+        if self.order.contains(&node) {
+            return;
+        }
+
+        self.connections[index].enabled = false;
+        self.insert(Connection {
+            innovation: innovations.connection(from, node),
+            from,
+            to: node,
+            weight: 1.0,
+            enabled: true,
+        });
+        self.insert(Connection {
+            innovation: innovations.connection(node, to),
+            from: node,
+            to,
+            weight,
+            enabled: true,
+        });
+        self.sort_nodes();
+    }
+
+    /// Orders the hidden and output nodes so that each comes after its inputs
+    fn sort_nodes(&mut self) {
+        let mut nodes: Vec<usize> = (INPUTS..INPUTS + OUTPUTS)
+            .chain(self.connections.iter().map(|connection| connection.to))
+            .collect();
+        nodes.sort_unstable();
+        nodes.dedup();
+
+        let mut visited = HashSet::new();
+        let mut order = Vec::with_capacity(nodes.len());
+        for node in nodes {
+            self.visit(node, &mut visited, &mut order);
+        }
+
+        self.order = order;
+    }
+
+    fn visit(&self, node: usize, visited: &mut HashSet<usize>, order: &mut Vec<usize>) {
+        if node < INPUTS || !visited.insert(node) {
+            return;
+        }
+
+        for connection in self.connections.iter().filter(|c| c.to == node) {
+            self.visit(connection.from, visited, order);
+        }
+        order.push(node);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grown_network(innovations: &mut Innovations) -> NeuralNetwork {
+        let mut network = NeuralNetwork::random();
+        for _ in 0..50 {
+            network.add_node(innovations);
+            network.add_connection(innovations);
+        }
+        network.sort_nodes();
+        network
+    }
+
+    #[test]
+    fn structural_mutations_keep_the_network_feed_forward() {
+        let mut innovations = Innovations::default();
+        let network = grown_network(&mut innovations);
+
+        assert!(network.hidden_nodes().count() > 0);
+        for connection in &network.connections {
+            let position = |node| network.order.iter().position(|&other| other == node);
+
+            assert!(
+                connection.from < INPUTS || position(connection.from) < position(connection.to)
+            );
+        }
+
+        let outputs = network.evaluate(&[0.5; INPUTS]);
+        assert!(outputs.iter().all(|output| output.abs() <= 1.0));
+    }
+
+    #[test]
+    fn crossover_with_itself_changes_nothing() {
+        let mut innovations = Innovations::default();
+        let network = grown_network(&mut innovations);
+        let offspring = NeuralNetwork::crossover(&network, &network);
+
+        assert_eq!(network.compatibility_distance(&offspring), 0.0);
+        assert_eq!(network.order, offspring.order);
+    }
+
+    #[test]
+    fn splitting_a_connection_adds_two_disjoint_connections() {
+        let mut innovations = Innovations::default();
+        let network = NeuralNetwork::random();
+        let mut split = network.clone();
+        split.add_node(&mut innovations);
+
+        let expected = DISJOINT_COEFFICIENT * 2.0 / split.connections.len() as f32;
+        assert_eq!(network.compatibility_distance(&split), expected);
+    }
+}
@@ -0,0 +1,306 @@
+use bevy::{prelude::*, sprite::MaterialMesh2dBundle};
+
+use crate::brain::{self, Brain};
+use crate::clock::SimClock;
+use crate::genome::{Genome, TrophicLevel};
+use crate::heat_diffusion::{HeatDiffusionSet, HeatSources, TemperatureField};
+use crate::lineage::{Lineage, Phylogeny};
+use crate::neat::{Innovations, NeuralNetwork};
+use crate::species::{self, SpeciesId};
+use crate::steering::{SteeringForce, Wander};
+
+const INITIAL_ENERGY: f32 = 100.0;
+const BASAL_METABOLIC_RATE: f32 = 0.15; // Energy per second for a body mass of 1
+const MOVEMENT_COST: f32 = 0.002; // Energy per second per unit of body mass * speed^2
+const THERMAL_COST: f32 = 0.01; // Energy per second per degree away from the preferred temperature
+const METABOLIC_HEAT_FRACTION: f32 = 0.5; // Fraction of basal and movement energy released as heat
+const BODY_HEAT_CONDUCTANCE: f32 = 0.01; // Heat exchanged per second per unit of body mass * degree
+const OFFSPRING_ENERGY_SHARE: f32 = 0.5; // Fraction of the parent's energy given to its offspring
+const REPRODUCTION_COST: f32 = 10.0; // Energy lost by the parent on top of the offspring's share
+
+pub struct OrganismPlugin {
+    pub world_size: Vec2,
+    /// Number of organisms spawned at startup
+    pub initial_count: usize,
+    pub carnivore_fraction: f32,
+    /// Fraction of the initial organisms born with a neural network deciding their behavior
+    pub brain_fraction: f32,
+    pub reproduction: Reproduction,
+}
+
+/// How organisms produce offspring
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reproduction {
+    /// Split off a mutated copy on their own
+    Asexual,
+    /// Find a compatible mate and mix both genomes, see `MatingPlugin`
+    Sexual,
+}
+
+/// Stages of the organism update, run in order every `FixedUpdate`
+#[derive(SystemSet, Debug, Hash, PartialEq, Eq, Clone)]
+pub enum OrganismSet {
+    /// Decide where to go by writing to `Velocity`
+    Steer,
+    Move,
+    /// Gain and spend energy
+    Metabolize,
+    /// Reproduction and death
+    Lifecycle,
+}
+
+impl Plugin for OrganismPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(OrganismConfig {
+            world_size: self.world_size,
+            initial_count: self.initial_count,
+            carnivore_fraction: self.carnivore_fraction,
+            brain_fraction: self.brain_fraction,
+        })
+        .insert_resource(self.reproduction)
+        .init_resource::<Innovations>()
+        .configure_sets(
+            FixedUpdate,
+            (
+                OrganismSet::Steer,
+                OrganismSet::Move,
+                OrganismSet::Metabolize,
+                OrganismSet::Lifecycle,
+            )
+                // `chain`ing sets together runs them in order
+                .chain(),
+        )
+        .add_systems(Startup, setup)
+        .add_systems(FixedUpdate, apply_velocity.in_set(OrganismSet::Move))
+        .add_systems(
+            FixedUpdate,
+            apply_metabolism
+                .in_set(OrganismSet::Metabolize)
+                .in_set(HeatDiffusionSet::Sources),
+        )
+        .add_systems(
+            FixedUpdate,
+            (
+                reproduce.run_if(resource_equals(Reproduction::Asexual)),
+                despawn_dead,
+            )
+                .chain()
+                .in_set(OrganismSet::Lifecycle),
+        );
+    }
+}
+
+#[derive(Resource)]
+struct OrganismConfig {
+    world_size: Vec2,
+    initial_count: usize,
+    carnivore_fraction: f32,
+    brain_fraction: f32,
+}
+
+/// Shared rendering assets used when spawning organisms
+#[derive(Resource)]
+pub struct OrganismAssets {
+    mesh: Handle<Mesh>,
+}
+
+#[derive(Component)]
+pub struct Organism;
+
+#[derive(Component, Deref, DerefMut)]
+pub struct Velocity(pub Vec2);
+
+#[derive(Component)]
+pub struct Energy(pub f32);
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut phylogeny: ResMut<Phylogeny>,
+    config: Res<OrganismConfig>,
+    clock: Res<SimClock>,
+) {
+    let assets = OrganismAssets {
+        mesh: meshes.add(Circle::default()),
+    };
+    let world_size = config.world_size;
+
+    (0..config.initial_count).for_each(|_| {
+        let position = Vec2::new(
+            rand::random::<f32>() * world_size.x - world_size.x / 2.0,
+            rand::random::<f32>() * world_size.y - world_size.y / 2.0,
+        );
+
+        let trophic_level = if rand::random::<f32>() < config.carnivore_fraction {
+            TrophicLevel::Carnivore
+        } else {
+            TrophicLevel::Herbivore
+        };
+
+        let mut genome = Genome::random(trophic_level);
+        if rand::random::<f32>() < config.brain_fraction {
+            genome.brain = Some(NeuralNetwork::random());
+        }
+
+        spawn_organism(
+            &mut commands,
+            &assets,
+            &mut materials,
+            genome,
+            position,
+            INITIAL_ENERGY,
+            phylogeny.birth(None, None, clock.elapsed()),
+            None,
+        );
+    });
+
+    commands.insert_resource(assets);
+}
+
+/// Spawns an organism. Offspring are born into their parent's `species`, and
+/// take its color, until the next clustering.
+pub fn spawn_organism(
+    commands: &mut Commands,
+    assets: &OrganismAssets,
+    materials: &mut Assets<ColorMaterial>,
+    genome: Genome,
+    position: Vec2,
+    energy: f32,
+    lineage: Lineage,
+    species: Option<SpeciesId>,
+) {
+    let scale = Vec3::new(genome.size, genome.size, 1.0);
+
+    // Start moving in a random direction at a random fraction of the maximum speed
+    let velocity = Vec2::from_angle(rand::random::<f32>() * std::f32::consts::TAU)
+        * genome.max_speed
+        * rand::random::<f32>();
+
+    let has_brain = genome.brain.is_some();
+    let color = species.map_or(genome.trophic_level.base_color(), |species| {
+        species::species_color(species, genome.trophic_level)
+    });
+
+    let mut organism = commands.spawn((
+        MaterialMesh2dBundle {
+            mesh: assets.mesh.clone().into(),
+            material: materials.add(color),
+            transform: Transform::from_translation(position.extend(1.0)).with_scale(scale),
+            ..default()
+        },
+        Organism,
+        Velocity(velocity),
+        SteeringForce::default(),
+        Wander::random(),
+        Energy(energy),
+        genome,
+        lineage,
+    ));
+
+    if has_brain {
+        organism.insert(Brain::default());
+    }
+    if let Some(species) = species {
+        organism.insert(species);
+    }
+}
+
+pub fn apply_velocity(mut query: Query<(&mut Transform, &Velocity)>, time: Res<Time>) {
+    for (mut transform, velocity) in &mut query {
+        transform.translation.x += velocity.x * time.delta_seconds();
+        transform.translation.y += velocity.y * time.delta_seconds();
+    }
+}
+
+/// Drains energy from every organism based on its body mass, how fast it is
+/// moving and how far the cell it is standing on is from its preferred temperature.
+///
+/// Organisms also exchange heat with that cell: part of the energy they burn is
+/// released as heat, and their bodies, kept at their preferred temperature,
+/// warm colder cells and cool warmer ones.
+fn apply_metabolism(
+    mut organisms: Query<(&Transform, &Velocity, &Genome, &mut Energy), With<Organism>>,
+    temperature_field: Res<TemperatureField>,
+    mut heat_sources: ResMut<HeatSources>,
+    time: Res<Time>,
+) {
+    for (transform, velocity, genome, mut energy) in organisms.iter_mut() {
+        let mass = genome.body_mass();
+
+        let cell = temperature_field
+            .geometry()
+            .world_to_cell(transform.translation.truncate());
+        let temperature = cell.map_or(genome.preferred_temperature, |(x, y)| {
+            temperature_field.get(x, y)
+        });
+
+        let basal_cost = BASAL_METABOLIC_RATE * mass.powf(0.75);
+        let movement_cost = MOVEMENT_COST * mass * velocity.length_squared();
+        let thermal_cost = THERMAL_COST * mass * (temperature - genome.preferred_temperature).abs();
+
+        energy.0 -= (basal_cost + movement_cost + thermal_cost) * time.delta_seconds();
+
+        if let Some((x, y)) = cell {
+            let metabolic_heat = METABOLIC_HEAT_FRACTION * (basal_cost + movement_cost);
+            let body_heat =
+                BODY_HEAT_CONDUCTANCE * mass * (genome.preferred_temperature - temperature);
+
+            heat_sources.add_heat(x, y, (metabolic_heat + body_heat) * time.delta_seconds());
+        }
+    }
+}
+
+/// Splits off an offspring with a mutated copy of the parent's genome once the
+/// parent has built up enough energy, unless its brain decides against it.
+fn reproduce(
+    mut commands: Commands,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    mut query: Query<
+        (
+            &Transform,
+            &Genome,
+            &Lineage,
+            &mut Energy,
+            Option<&Brain>,
+            Option<&SpeciesId>,
+        ),
+        With<Organism>,
+    >,
+    mut innovations: ResMut<Innovations>,
+    mut phylogeny: ResMut<Phylogeny>,
+    assets: Res<OrganismAssets>,
+    clock: Res<SimClock>,
+) {
+    for (transform, genome, lineage, mut energy, brain, species) in query.iter_mut() {
+        if energy.0 < genome.reproduction_threshold || !brain::wants_to_reproduce(brain) {
+            continue;
+        }
+
+        let offspring_energy = energy.0 * OFFSPRING_ENERGY_SHARE;
+        energy.0 -= offspring_energy + REPRODUCTION_COST;
+
+        // Place the offspring just outside of the parent's body
+        let offset = Vec2::from_angle(rand::random::<f32>() * std::f32::consts::TAU) * genome.size;
+
+        spawn_organism(
+            &mut commands,
+            &assets,
+            &mut materials,
+            genome.mutate(&mut innovations),
+            transform.translation.truncate() + offset,
+            offspring_energy,
+            phylogeny.birth(Some(lineage), None, clock.elapsed()),
+            species.copied(),
+        );
+    }
+}
+
+/// Removes organisms that have starved or been eaten
+fn despawn_dead(mut commands: Commands, query: Query<(Entity, &Energy), With<Organism>>) {
+    for (entity, energy) in query.iter() {
+        if energy.0 <= 0.0 {
+            commands.entity(entity).despawn();
+        }
+    }
+}
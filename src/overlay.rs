@@ -0,0 +1,110 @@
+use bevy::{
+    prelude::*,
+    render::{
+        render_asset::RenderAssetUsages,
+        render_resource::{Extent3d, TextureDimension, TextureFormat},
+        texture::ImageSampler,
+    },
+};
+
+use crate::field::{Field, GridGeometry};
+
+pub struct OverlayPlugin;
+
+/// Which per-cell value the grid overlay is currently colored by
+#[derive(Resource, Debug, Default, PartialEq, Eq, Clone, Copy)]
+pub enum Overlay {
+    #[default]
+    Temperature,
+    Vegetation,
+    Biome,
+    /// A `DiffusingField`, by name
+    Field(&'static str),
+}
+
+/// Image covering the grid with one pixel per cell
+#[derive(Resource)]
+pub struct OverlayImage(Handle<Image>);
+
+impl Plugin for OverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Overlay::default())
+            .add_systems(Startup, setup)
+            .add_systems(Update, set_overlay);
+    }
+}
+
+fn setup(mut commands: Commands, mut images: ResMut<Assets<Image>>, geometry: Res<GridGeometry>) {
+    let handle = images.add(grid_image(&geometry));
+
+    commands.spawn(grid_sprite(handle.clone(), &geometry, 0.0));
+    commands.insert_resource(OverlayImage(handle));
+}
+
+/// A black image with one pixel per cell of the grid
+pub fn grid_image(geometry: &GridGeometry) -> Image {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: geometry.width as u32,
+            height: geometry.height as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    // Keep cells crisp instead of blurring them together
+    image.sampler = ImageSampler::nearest();
+
+    image
+}
+
+/// A sprite stretching a grid image over the grid's bounds at the given depth
+pub fn grid_sprite(texture: Handle<Image>, geometry: &GridGeometry, z: f32) -> SpriteBundle {
+    let bounds = geometry.bounds();
+
+    SpriteBundle {
+        texture,
+        sprite: Sprite {
+            custom_size: Some(bounds.size()),
+            ..Default::default()
+        },
+        transform: Transform::from_translation(bounds.center().extend(z)),
+        ..Default::default()
+    }
+}
+
+/// Colors every pixel of the overlay from the value of its cell in `field`
+pub fn paint<T: Copy>(
+    images: &mut Assets<Image>,
+    overlay_image: &OverlayImage,
+    field: &Field<T>,
+    color: impl Fn(T) -> Color,
+) {
+    if let Some(image) = images.get_mut(&overlay_image.0) {
+        paint_image(image, field, color);
+    }
+}
+
+/// Colors every pixel of a grid image from the value of its cell in `field`
+pub fn paint_image<T: Copy>(image: &mut Image, field: &Field<T>, color: impl Fn(T) -> Color) {
+    for (index, &value) in field.values().iter().enumerate() {
+        let x = index % field.width();
+        let y = index / field.width();
+        // Image rows start at the top, while the grid's first row is at the bottom
+        let pixel = ((field.height() - 1 - y) * field.width() + x) * 4;
+
+        image.data[pixel..pixel + 4].copy_from_slice(&color(value).to_srgba().to_u8_array());
+    }
+}
+
+fn set_overlay(mut overlay: ResMut<Overlay>, keyboard_input: Res<ButtonInput<KeyCode>>) {
+    if keyboard_input.just_pressed(KeyCode::Digit1) {
+        *overlay = Overlay::Temperature;
+    } else if keyboard_input.just_pressed(KeyCode::Digit2) {
+        *overlay = Overlay::Vegetation;
+    } else if keyboard_input.just_pressed(KeyCode::Digit4) {
+        *overlay = Overlay::Biome;
+    }
+}
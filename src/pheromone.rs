@@ -0,0 +1,164 @@
+use bevy::prelude::*;
+
+use crate::boundary::{self, BoundaryMode};
+use crate::brain::Brain;
+use crate::diffusion::{Concentration, DiffusingField, DiffusionPlugin, DiffusionSet, Sources};
+use crate::field::Field;
+use crate::genome::{Genome, TrophicLevel};
+use crate::organism::{Energy, Organism, OrganismSet, Velocity};
+use crate::steering::{self, SteeringForce};
+use crate::vegetation::{BiomassField, MAXIMUM_BIOMASS};
+use crate::wind::WindField;
+
+const FOOD_TRAIL_DEPOSIT: f32 = 2.0; // Food trail laid per second by a herbivore on a fully grown cell
+const MATING_DEPOSIT: f32 = 1.0; // Mating scent laid per second by an organism ready to reproduce
+const HUNGER: f32 = 0.5; // Organisms with less than this fraction of their reproduction threshold seek food
+const MATING_READINESS: f32 = 0.8; // Organisms with more than this fraction of their reproduction threshold seek mates
+
+pub struct PheromonePlugin {
+    /// Distance from the organism at which pheromone gradients are sampled
+    pub sensing_radius: f32,
+    /// Weight of following pheromones against the organism's other steering behaviors
+    pub strength: f32,
+}
+
+impl Plugin for PheromonePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_plugins((
+            DiffusionPlugin::<FoodTrail>::default(),
+            DiffusionPlugin::<Alarm>::default(),
+            DiffusionPlugin::<MatingScent>::default(),
+        ))
+        .insert_resource(PheromoneConfig {
+            sensing_radius: self.sensing_radius,
+            strength: self.strength,
+        })
+        .add_systems(
+            FixedUpdate,
+            lay_pheromones
+                .in_set(OrganismSet::Move)
+                .in_set(DiffusionSet::Sources),
+        )
+        .add_systems(FixedUpdate, follow_pheromones.in_set(OrganismSet::Steer));
+    }
+}
+
+/// Laid by herbivores where they find food, and followed by hungry herbivores
+pub struct FoodTrail;
+
+impl DiffusingField for FoodTrail {
+    const NAME: &'static str = "food trail";
+    const DIFFUSIVITY: f32 = 0.05;
+    const DECAY: f32 = 0.05;
+    const OVERLAY_KEY: KeyCode = KeyCode::Digit5;
+
+    fn color(value: f32) -> Color {
+        Color::srgb(0.0, (value / 20.0).min(1.0), 0.0)
+    }
+}
+
+/// Laid by prey fleeing a predator, and avoided by herbivores
+pub struct Alarm;
+
+impl DiffusingField for Alarm {
+    const NAME: &'static str = "alarm";
+    const DIFFUSIVITY: f32 = 0.2;
+    const DECAY: f32 = 0.5;
+    const OVERLAY_KEY: KeyCode = KeyCode::Digit6;
+
+    fn color(value: f32) -> Color {
+        Color::srgb((value / 5.0).min(1.0), 0.0, 0.0)
+    }
+
+    fn flow(wind: &WindField) -> Option<&Field<Vec2>> {
+        Some(wind)
+    }
+}
+
+/// Laid and followed by organisms ready to reproduce
+pub struct MatingScent;
+
+impl DiffusingField for MatingScent {
+    const NAME: &'static str = "mating scent";
+    const DIFFUSIVITY: f32 = 0.1;
+    const DECAY: f32 = 0.2;
+    const OVERLAY_KEY: KeyCode = KeyCode::Digit7;
+
+    fn color(value: f32) -> Color {
+        let ratio = (value / 10.0).min(1.0);
+
+        Color::srgb(ratio, 0.4 * ratio, 0.7 * ratio)
+    }
+
+    fn flow(wind: &WindField) -> Option<&Field<Vec2>> {
+        Some(wind)
+    }
+}
+
+#[derive(Resource)]
+struct PheromoneConfig {
+    sensing_radius: f32,
+    strength: f32,
+}
+
+fn lay_pheromones(
+    organisms: Query<(&Transform, &Genome, &Energy), With<Organism>>,
+    mut food_trail: ResMut<Sources<FoodTrail>>,
+    mut mating_scent: ResMut<Sources<MatingScent>>,
+    biomass: Res<BiomassField>,
+    time: Res<Time>,
+) {
+    let dt = time.delta_seconds();
+
+    for (transform, genome, energy) in organisms.iter() {
+        let position = transform.translation.truncate();
+
+        if genome.trophic_level == TrophicLevel::Herbivore {
+            let food = biomass.sample(position).unwrap_or(0.0) / MAXIMUM_BIOMASS;
+            food_trail.deposit(position, FOOD_TRAIL_DEPOSIT * food * dt);
+        }
+        if energy.0 >= genome.reproduction_threshold * MATING_READINESS {
+            mating_scent.deposit(position, MATING_DEPOSIT * dt);
+        }
+    }
+}
+
+/// Steers organisms up the gradients of the pheromones they are attracted to and
+/// down the gradients of those they avoid
+fn follow_pheromones(
+    mut organisms: Query<
+        (&Transform, &Genome, &Energy, &Velocity, &mut SteeringForce),
+        (With<Organism>, Without<Brain>),
+    >,
+    food_trail: Res<Concentration<FoodTrail>>,
+    alarm: Res<Concentration<Alarm>>,
+    mating_scent: Res<Concentration<MatingScent>>,
+    config: Res<PheromoneConfig>,
+    mode: Option<Res<BoundaryMode>>,
+) {
+    let radius = config.sensing_radius;
+    let wrap = boundary::world_wrap(mode.as_deref());
+
+    for (transform, genome, energy, velocity, mut force) in organisms.iter_mut() {
+        let position = transform.translation.truncate();
+        let is_herbivore = genome.trophic_level == TrophicLevel::Herbivore;
+        let direction = |gradient: Vec2| gradient.normalize_or_zero();
+
+        let mut desired = Vec2::ZERO;
+        if is_herbivore {
+            desired -= direction(alarm.gradient(position, radius, wrap));
+        }
+        if is_herbivore && energy.0 < genome.reproduction_threshold * HUNGER {
+            desired += direction(food_trail.gradient(position, radius, wrap));
+        }
+        if energy.0 >= genome.reproduction_threshold * MATING_READINESS {
+            desired += direction(mating_scent.gradient(position, radius, wrap));
+        }
+
+        if desired == Vec2::ZERO {
+            continue;
+        }
+
+        force.0 += steering::steer_towards(velocity.0, desired, genome.max_speed) * config.strength;
+    }
+}
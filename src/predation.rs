@@ -0,0 +1,197 @@
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::brain::{self, Brain};
+use crate::diffusion::{DiffusionSet, Sources};
+use crate::genome::{Genome, TrophicLevel};
+use crate::organism::{Energy, Organism, OrganismSet, Velocity};
+use crate::pheromone::Alarm;
+use crate::spatial::SpatialIndex;
+use crate::steering::{self, SteeringForce};
+
+const MEAT_ENERGY: f32 = 40.0; // Energy stored in the body of a prey with a body mass of 1
+const ALARM_DEPOSIT: f32 = 5.0; // Alarm pheromone laid per second by prey fleeing a predator
+
+pub struct PredationPlugin {
+    /// How far organisms can see predators and prey
+    pub detection_radius: f32,
+    /// Prey must be at most this fraction of a carnivore's size to be eaten
+    pub prey_size_ratio: f32,
+    /// Fraction of the prey's energy and body converted into energy for the carnivore
+    pub digestion_efficiency: f32,
+}
+
+impl Plugin for PredationPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PredationConfig {
+            detection_radius: self.detection_radius,
+            prey_size_ratio: self.prey_size_ratio,
+            digestion_efficiency: self.digestion_efficiency,
+        })
+        .add_systems(
+            FixedUpdate,
+            hunt_and_flee
+                .in_set(OrganismSet::Steer)
+                .in_set(DiffusionSet::Sources),
+        )
+        .add_systems(FixedUpdate, eat_prey.in_set(OrganismSet::Metabolize));
+    }
+}
+
+#[derive(Resource)]
+pub struct PredationConfig {
+    detection_radius: f32,
+    prey_size_ratio: f32,
+    digestion_efficiency: f32,
+}
+
+impl PredationConfig {
+    fn can_eat(&self, predator: &Genome, prey: &Genome) -> bool {
+        predator.trophic_level == TrophicLevel::Carnivore
+            && prey.size <= predator.size * self.prey_size_ratio
+    }
+
+    pub fn detection_radius(&self) -> f32 {
+        self.detection_radius
+    }
+
+    /// Positions of the nearest organism that could eat `entity` and the nearest
+    /// organism it could eat, among those it can see
+    pub fn nearest_threat_and_prey(
+        &self,
+        entity: Entity,
+        position: Vec2,
+        genome: &Genome,
+        genomes: &Query<&Genome, With<Organism>>,
+        index: &SpatialIndex,
+    ) -> (Option<Vec2>, Option<Vec2>) {
+        let mut threat: Option<(f32, Vec2)> = None;
+        let mut prey: Option<(f32, Vec2)> = None;
+
+        for (other, other_position) in index.within_radius(position, self.detection_radius) {
+            if other == entity {
+                continue;
+            }
+            let Ok(other_genome) = genomes.get(other) else {
+                continue;
+            };
+
+            let distance_squared = other_position.distance_squared(position);
+            let is_closer = |nearest: Option<(f32, Vec2)>| {
+                nearest.filter(|(d, _)| *d <= distance_squared).is_none()
+            };
+
+            if self.can_eat(other_genome, genome) && is_closer(threat) {
+                threat = Some((distance_squared, other_position));
+            }
+            if self.can_eat(genome, other_genome) && is_closer(prey) {
+                prey = Some((distance_squared, other_position));
+            }
+        }
+
+        (threat.map(|(_, at)| at), prey.map(|(_, at)| at))
+    }
+}
+
+/// Steers prey away from the nearest predator they can see and carnivores
+/// towards the nearest prey they can see. Fleeing takes priority over hunting.
+/// Fleeing prey raise the alarm for others nearby, even when their brain rather
+/// than this behavior decides where they go.
+pub fn hunt_and_flee(
+    mut query: Query<
+        (
+            Entity,
+            &Transform,
+            &Genome,
+            &Velocity,
+            &mut SteeringForce,
+            Has<Brain>,
+        ),
+        With<Organism>,
+    >,
+    genomes: Query<&Genome, With<Organism>>,
+    mut alarm: ResMut<Sources<Alarm>>,
+    index: Res<SpatialIndex>,
+    config: Res<PredationConfig>,
+    time: Res<Time>,
+) {
+    for (entity, transform, genome, velocity, mut force, has_brain) in query.iter_mut() {
+        let position = transform.translation.truncate();
+        let (threat, target) =
+            config.nearest_threat_and_prey(entity, position, genome, &genomes, &index);
+
+        if threat.is_some() {
+            alarm.deposit(position, ALARM_DEPOSIT * time.delta_seconds());
+        }
+        if has_brain {
+            continue;
+        }
+
+        let weights = genome.steering;
+        if let Some(threat) = threat {
+            force.0 +=
+                steering::flee(position, velocity.0, threat, genome.max_speed) * weights.flee;
+        } else if let Some(target) = target {
+            force.0 +=
+                steering::seek(position, velocity.0, target, genome.max_speed) * weights.seek;
+        }
+    }
+}
+
+/// Carnivores eat one prey they touch per tick. The prey's energy is drained to
+/// zero so that it is removed along with the organisms that starved.
+fn eat_prey(
+    mut query: Query<(Entity, &Transform, &Genome, &mut Energy, Option<&Brain>), With<Organism>>,
+    index: Res<SpatialIndex>,
+    config: Res<PredationConfig>,
+) {
+    let mut eaten = HashSet::new();
+    let mut meals = Vec::new();
+
+    for (predator, predator_transform, predator_genome, _, predator_brain) in query.iter() {
+        if predator_genome.trophic_level != TrophicLevel::Carnivore
+            || eaten.contains(&predator)
+            || !brain::wants_to_eat(predator_brain)
+        {
+            continue;
+        }
+
+        let position = predator_transform.translation.truncate();
+
+        // Prey is never larger than the predator, so it can be touched from at most this far
+        let max_reach = predator_genome.size;
+
+        let meal = index
+            .within_radius(position, max_reach)
+            .find(|(prey, prey_position)| {
+                let Ok((_, _, prey_genome, prey_energy, _)) = query.get(*prey) else {
+                    return false;
+                };
+                // Organisms are circles whose diameter is their size
+                let reach = (predator_genome.size + prey_genome.size) / 2.0;
+
+                *prey != predator
+                    && prey_energy.0 > 0.0
+                    && !eaten.contains(prey)
+                    && config.can_eat(predator_genome, prey_genome)
+                    && prey_position.distance(position) <= reach
+            });
+
+        if let Some((prey, _)) = meal {
+            eaten.insert(prey);
+            meals.push((predator, prey));
+        }
+    }
+
+    for (predator, prey) in meals {
+        let Ok((_, _, prey_genome, mut prey_energy, _)) = query.get_mut(prey) else {
+            continue;
+        };
+
+        let meat = prey_energy.0 + prey_genome.body_mass() * MEAT_ENERGY;
+        prey_energy.0 = 0.0;
+
+        if let Ok((_, _, _, mut predator_energy, _)) = query.get_mut(predator) {
+            predator_energy.0 += meat * config.digestion_efficiency;
+        }
+    }
+}
@@ -0,0 +1,356 @@
+use bevy::prelude::*;
+
+use crate::boundary::{self, BoundaryMode};
+use crate::field::GridGeometry;
+use crate::organism::{Organism, OrganismSet};
+
+pub struct SpatialIndexPlugin;
+
+impl Plugin for SpatialIndexPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SpatialIndex::default()).add_systems(
+            FixedUpdate,
+            rebuild_spatial_index.before(OrganismSet::Steer),
+        );
+    }
+}
+
+/// Uniform grid bucketing organisms by the grid cell they are over.
+///
+/// Organisms outside of the grid are stored in the nearest edge cell so that
+/// they can still be found by neighborhood queries. Along axes that wrap, queries
+/// reach around to the opposite edge and measure distances the short way around.
+#[derive(Resource, Default)]
+pub struct SpatialIndex {
+    geometry: GridGeometry,
+    wrap: BVec2,
+    // Row-major buckets of entities and their positions
+    cells: Vec<Vec<(Entity, Vec2)>>,
+}
+
+impl SpatialIndex {
+    fn resize(&mut self, geometry: GridGeometry, wrap: BVec2) {
+        self.geometry = geometry;
+        self.wrap = wrap;
+        self.cells.resize_with(geometry.len(), Vec::new);
+
+        for cell in self.cells.iter_mut() {
+            cell.clear();
+        }
+    }
+
+    fn insert(&mut self, entity: Entity, position: Vec2) {
+        if self.cells.is_empty() {
+            return;
+        }
+
+        let (x, y) = self.geometry.world_to_cell_clamped(position);
+        let index = self.geometry.index(x, y);
+        self.cells[index].push((entity, position));
+    }
+
+    /// Entities bucketed in the given cell
+    pub fn entities_in_cell(&self, x: usize, y: usize) -> &[(Entity, Vec2)] {
+        &self.cells[self.geometry.index(x, y)]
+    }
+
+    /// Shortest offset from one position to another, going around the wrapping axes
+    fn offset(&self, from: Vec2, to: Vec2) -> Vec2 {
+        let size = self.geometry.bounds().size();
+        let offset = to - from;
+
+        Vec2::select(self.wrap, offset - size * (offset / size).round(), offset)
+    }
+
+    /// All entities within `radius` of `position`.
+    ///
+    /// Entities found across a wrapping edge are given at their position as seen
+    /// from `position`, i.e. offset by the size of the world.
+    pub fn within_radius(
+        &self,
+        position: Vec2,
+        radius: f32,
+    ) -> impl Iterator<Item = (Entity, Vec2)> + '_ {
+        let radius_squared = radius * radius;
+        let (columns, rows) = if self.cells.is_empty() {
+            (Vec::new(), Vec::new())
+        } else {
+            let min = (position - radius - self.geometry.origin) / self.geometry.cell_size;
+            let max = (position + radius - self.geometry.origin) / self.geometry.cell_size;
+            (
+                axis_cells(min.x, max.x, self.geometry.width, self.wrap.x),
+                axis_cells(min.y, max.y, self.geometry.height, self.wrap.y),
+            )
+        };
+
+        rows.into_iter()
+            .flat_map(move |y| columns.clone().into_iter().map(move |x| (x, y)))
+            .flat_map(move |(x, y)| self.entities_in_cell(x, y).iter().copied())
+            .map(move |(entity, other)| (entity, self.offset(position, other)))
+            .filter(move |(_, offset)| offset.length_squared() <= radius_squared)
+            .map(move |(entity, offset)| (entity, position + offset))
+    }
+
+    /// The `k` entities closest to `position`, nearest first. Positions are given
+    /// as seen from `position`, like in `within_radius`.
+    pub fn k_nearest(&self, position: Vec2, k: usize) -> Vec<(Entity, Vec2)> {
+        let mut nearest: Vec<(f32, Entity, Vec2)> = Vec::new();
+
+        if self.cells.is_empty() || k == 0 {
+            return Vec::new();
+        }
+
+        // Bring the query into the grid along the axes that wrap
+        let query = self.geometry.wrap_position(position, self.wrap);
+
+        let (center_x, center_y) = self.geometry.world_to_cell_clamped(query);
+        let (center_x, center_y) = (center_x as isize, center_y as isize);
+        // Range of cell offsets from the center that reach every cell exactly once
+        let (lowest_x, highest_x) = offset_range(center_x, self.geometry.width, self.wrap.x);
+        let (lowest_y, highest_y) = offset_range(center_y, self.geometry.height, self.wrap.y);
+        let max_ring = self.geometry.width.max(self.geometry.height) as isize;
+
+        // Visit the rings of cells around the center cell from the inside out
+        for ring in 0..=max_ring {
+            let min_x = (-ring).max(lowest_x);
+            let max_x = ring.min(highest_x);
+            let min_y = (-ring).max(lowest_y);
+            let max_y = ring.min(highest_y);
+
+            for y in min_y..=max_y {
+                for x in min_x..=max_x {
+                    if x.abs() != ring && y.abs() != ring {
+                        continue;
+                    }
+
+                    let cell_x = (center_x + x).rem_euclid(self.geometry.width as isize);
+                    let cell_y = (center_y + y).rem_euclid(self.geometry.height as isize);
+                    for &(entity, other) in self.entities_in_cell(cell_x as usize, cell_y as usize)
+                    {
+                        let offset = self.offset(position, other);
+                        nearest.push((offset.length_squared(), entity, position + offset));
+                    }
+                }
+            }
+
+            // Anything not visited yet lies past one of the sides of the searched
+            // cells that are not also edges of the grid. Across a wrapping axis it
+            // may be reached the other way around, past the opposite side.
+            let searched_min = self.geometry.origin
+                + Vec2::new((center_x + min_x) as f32, (center_y + min_y) as f32)
+                    * self.geometry.cell_size;
+            let searched_max = self.geometry.origin
+                + Vec2::new((center_x + max_x + 1) as f32, (center_y + max_y + 1) as f32)
+                    * self.geometry.cell_size;
+            let complete_x = min_x == lowest_x && max_x == highest_x;
+            let complete_y = min_y == lowest_y && max_y == highest_y;
+            let mut unsearched_distance = f32::INFINITY;
+            if min_x > lowest_x || (self.wrap.x && !complete_x) {
+                unsearched_distance = unsearched_distance.min(query.x - searched_min.x);
+            }
+            if max_x < highest_x || (self.wrap.x && !complete_x) {
+                unsearched_distance = unsearched_distance.min(searched_max.x - query.x);
+            }
+            if min_y > lowest_y || (self.wrap.y && !complete_y) {
+                unsearched_distance = unsearched_distance.min(query.y - searched_min.y);
+            }
+            if max_y < highest_y || (self.wrap.y && !complete_y) {
+                unsearched_distance = unsearched_distance.min(searched_max.y - query.y);
+            }
+
+            if unsearched_distance == f32::INFINITY {
+                break;
+            }
+
+            if nearest.len() < k {
+                continue;
+            }
+
+            nearest.sort_by(|a, b| a.0.total_cmp(&b.0));
+            nearest.truncate(k);
+
+            if nearest[k - 1].0 <= unsearched_distance * unsearched_distance {
+                break;
+            }
+        }
+
+        nearest.sort_by(|a, b| a.0.total_cmp(&b.0));
+        nearest
+            .into_iter()
+            .take(k)
+            .map(|(_, entity, position)| (entity, position))
+            .collect()
+    }
+}
+
+/// Cells along one axis of length `len` overlapping `min..=max`, in cell units.
+/// They are taken around the axis if it wraps, or clamped to it otherwise.
+fn axis_cells(min: f32, max: f32, len: usize, wrap: bool) -> Vec<usize> {
+    let last = len as isize - 1;
+    let (min, max) = (min.floor() as isize, max.floor() as isize);
+    let (min, max) = match wrap {
+        true if max - min >= last => (0, last),
+        true => (min, max),
+        false => (min.clamp(0, last), max.clamp(0, last)),
+    };
+
+    (min..=max)
+        .map(|cell| cell.rem_euclid(len as isize) as usize)
+        .collect()
+}
+
+/// Lowest and highest offsets from the `center` cell along an axis of length
+/// `len` that stay on the grid, or that go halfway around it if it wraps
+fn offset_range(center: isize, len: usize, wrap: bool) -> (isize, isize) {
+    let len = len as isize;
+
+    if wrap {
+        (-(len - 1) / 2, len / 2)
+    } else {
+        (-center, len - 1 - center)
+    }
+}
+
+fn rebuild_spatial_index(
+    mut index: ResMut<SpatialIndex>,
+    query: Query<(Entity, &Transform), With<Organism>>,
+    geometry: Res<GridGeometry>,
+    mode: Option<Res<BoundaryMode>>,
+) {
+    index.resize(*geometry, boundary::world_wrap(mode.as_deref()));
+
+    for (entity, transform) in query.iter() {
+        index.insert(entity, transform.translation.truncate());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Entities scattered over a 10 by 8 grid of 4 unit cells. Without wrapping
+    /// some of them are past its edges.
+    fn test_index(wrap: BVec2) -> SpatialIndex {
+        let geometry = GridGeometry::new(10, 8, 4.0, Vec2::new(40.0, 32.0));
+        let bounds = geometry.bounds();
+        let mut index = SpatialIndex::default();
+        index.resize(geometry, wrap);
+
+        for i in 0..150 {
+            let position = scattered(i, Vec2::splat(28.0));
+            let wrapped = bounds.min + (position - bounds.min).rem_euclid(bounds.size());
+            index.insert(Entity::from_raw(i), Vec2::select(wrap, wrapped, position));
+        }
+
+        index
+    }
+    /// Deterministic points spread within `extent` of the origin
+    fn scattered(i: u32, extent: Vec2) -> Vec2 {
+        let x = ((i * 7919) % 1000) as f32 / 500.0 - 1.0;
+        let y = ((i * 6841 + 311) % 1000) as f32 / 500.0 - 1.0;
+
+        Vec2::new(x, y) * extent
+    }
+
+    /// Query positions in the middle, on the edges and corners, and off the grid
+    fn test_queries() -> Vec<Vec2> {
+        let mut queries = vec![
+            Vec2::ZERO,
+            Vec2::new(-22.0, 0.0),
+            Vec2::new(18.0, -18.0),
+            Vec2::new(-22.0, 18.0),
+            Vec2::new(60.0, 5.0),
+            Vec2::new(-45.0, -40.0),
+            Vec2::new(3.0, 90.0),
+        ];
+        queries.extend((0..20).map(|i| scattered(i + 1000, Vec2::splat(35.0))));
+        queries
+    }
+
+    fn sorted_entities(mut entities: Vec<(Entity, Vec2)>) -> Vec<Entity> {
+        entities.sort_by_key(|(entity, _)| *entity);
+        entities.into_iter().map(|(entity, _)| entity).collect()
+    }
+
+    /// Every entity at its position as seen from `position`
+    fn all_entities(index: &SpatialIndex, position: Vec2) -> Vec<(Entity, Vec2)> {
+        index
+            .cells
+            .iter()
+            .flatten()
+            .map(|&(entity, other)| (entity, position + index.offset(position, other)))
+            .collect()
+    }
+
+    fn wrap_modes() -> [BVec2; 3] {
+        [BVec2::FALSE, BVec2::TRUE, BVec2::new(true, false)]
+    }
+
+    #[test]
+    fn within_radius_matches_brute_force() {
+        for wrap in wrap_modes() {
+            let index = test_index(wrap);
+
+            for position in test_queries() {
+                for radius in [0.0, 3.0, 9.5, 30.0, 200.0] {
+                    let expected = all_entities(&index, position)
+                        .into_iter()
+                        .filter(|(_, other)| other.distance(position) <= radius)
+                        .collect();
+                    let found = index.within_radius(position, radius).collect();
+
+                    assert_eq!(
+                        sorted_entities(found),
+                        sorted_entities(expected),
+                        "within {radius} of {position}, wrapping {wrap}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn k_nearest_matches_brute_force() {
+        for wrap in wrap_modes() {
+            let index = test_index(wrap);
+
+            for position in test_queries() {
+                for k in [1, 5, 17, 150, 200] {
+                    let mut expected = all_entities(&index, position);
+                    expected.sort_by(|a, b| {
+                        a.1.distance_squared(position)
+                            .total_cmp(&b.1.distance_squared(position))
+                    });
+                    expected.truncate(k);
+                    let found = index.k_nearest(position, k);
+
+                    let distances = |entities: &[(Entity, Vec2)]| -> Vec<f32> {
+                        entities
+                            .iter()
+                            .map(|(_, other)| other.distance(position))
+                            .collect()
+                    };
+                    assert_eq!(
+                        distances(&found),
+                        distances(&expected),
+                        "{k} nearest to {position}, wrapping {wrap}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn finds_neighbors_across_wrapping_edges() {
+        let geometry = GridGeometry::new(10, 8, 4.0, Vec2::new(40.0, 32.0));
+        let bounds = geometry.bounds();
+        let mut index = SpatialIndex::default();
+        index.resize(geometry, BVec2::TRUE);
+        index.insert(Entity::from_raw(0), bounds.max - 1.0);
+
+        let found: Vec<_> = index.within_radius(bounds.min + 1.0, 3.0).collect();
+
+        assert_eq!(found.len(), 1);
+        assert!(found[0].1.distance(bounds.min - 1.0) < 1e-4);
+    }
+}
@@ -0,0 +1,345 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    path::PathBuf,
+    time::Duration,
+};
+
+use bevy::{prelude::*, time::common_conditions::on_timer, utils::HashMap};
+
+use crate::clock::SimClock;
+use crate::export;
+use crate::genome::{Genome, TrophicLevel};
+use crate::organism::{Organism, OrganismSet};
+
+const HUE_STEP: f32 = 137.508; // Golden angle in degrees, keeping the hues of consecutive species apart
+const HUE_SPREAD: f32 = 90.0; // Width in degrees of the band of hues around the trophic level's color
+
+pub struct SpeciesPlugin {
+    /// Simulated seconds between two clusterings of the organisms into species
+    pub interval: f32,
+    /// File the speciation and extinction events are written to when the app exits
+    pub log_path: PathBuf,
+}
+
+impl Plugin for SpeciesPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SpeciesConfig {
+            log_path: self.log_path.clone(),
+        })
+        .init_resource::<SpeciesRegistry>()
+        .init_resource::<SpeciesLog>()
+        // Cluster the initial organisms right away so that they start in their species' colors
+        .add_systems(PostStartup, cluster_species)
+        .add_systems(
+            FixedUpdate,
+            cluster_species
+                .after(OrganismSet::Lifecycle)
+                .run_if(on_timer(Duration::from_secs_f32(self.interval))),
+        )
+        .add_systems(Last, export_log);
+    }
+}
+
+#[derive(Resource)]
+struct SpeciesConfig {
+    log_path: PathBuf,
+}
+
+/// Species an organism was assigned to at the last clustering
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct SpeciesId(pub u32);
+
+struct Species {
+    /// Genome that organisms are compared to when they are clustered
+    representative: Genome,
+    color: Color,
+}
+
+/// Living species, clustered NEAT-style: every organism joins the first species
+/// whose representative is genetically compatible with it, or founds a new one.
+///
+/// This is the speciation of the NEAT brains too. Genetic distance includes the
+/// compatibility distance of the brains, so organisms whose networks have grown
+/// apart are split into separate species even if the rest of their genes match.
+#[derive(Resource, Default)]
+struct SpeciesRegistry {
+    species: BTreeMap<SpeciesId, Species>,
+    next_id: u32,
+}
+
+impl SpeciesRegistry {
+    /// Species of an organism that was in `previous`. It stays there for as long
+    /// as it is compatible with that species, so species IDs are stable.
+    fn classify(&self, genome: &Genome, previous: Option<SpeciesId>) -> Option<SpeciesId> {
+        let is_member = |id: &SpeciesId| {
+            self.species
+                .get(id)
+                .is_some_and(|species| genome.is_compatible(&species.representative))
+        };
+
+        previous
+            .filter(is_member)
+            .or_else(|| self.species.keys().copied().find(is_member))
+    }
+
+    fn found(&mut self, founder: &Genome) -> SpeciesId {
+        let id = SpeciesId(self.next_id);
+        self.next_id += 1;
+
+        self.species.insert(
+            id,
+            Species {
+                representative: founder.clone(),
+                color: species_color(id, founder.trophic_level),
+            },
+        );
+        id
+    }
+
+    /// Sorts organisms, given by their genome and previous species, into species,
+    /// founding new species for organisms that are incompatible with all existing
+    /// ones and retiring species left without members. Returns the species of
+    /// every organism, in order.
+    fn cluster<'a>(
+        &mut self,
+        organisms: impl IntoIterator<Item = (&'a Genome, Option<SpeciesId>)>,
+        time: f64,
+        log: &mut SpeciesLog,
+    ) -> Vec<SpeciesId> {
+        let mut representatives = HashMap::new();
+
+        let assigned = organisms
+            .into_iter()
+            .map(|(genome, previous)| {
+                let id = self.classify(genome, previous).unwrap_or_else(|| {
+                    let id = self.found(genome);
+
+                    log.events.push(SpeciesEvent {
+                        time,
+                        species: id,
+                        kind: SpeciesEventKind::Speciation { parent: previous },
+                    });
+                    id
+                });
+
+                // The next clustering compares organisms to one of this clustering's members
+                representatives.entry(id).or_insert_with(|| genome.clone());
+                id
+            })
+            .collect();
+
+        self.species.retain(|&id, species| {
+            let Some(representative) = representatives.remove(&id) else {
+                log.events.push(SpeciesEvent {
+                    time,
+                    species: id,
+                    kind: SpeciesEventKind::Extinction,
+                });
+                return false;
+            };
+
+            species.representative = representative;
+            true
+        });
+
+        assigned
+    }
+}
+
+/// Stable color of a species, with a hue near that of its trophic level
+pub fn species_color(id: SpeciesId, trophic_level: TrophicLevel) -> Color {
+    let base = Hsla::from(trophic_level.base_color());
+    let offset = (id.0 as f32 * HUE_STEP).rem_euclid(HUE_SPREAD) - HUE_SPREAD / 2.0;
+
+    Hsla {
+        hue: (base.hue + offset).rem_euclid(360.0),
+        ..base
+    }
+    .into()
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SpeciesEventKind {
+    /// A species split off `parent`, the species its founder belonged to. Species
+    /// founded by organisms that were not in any species yet have no parent.
+    Speciation { parent: Option<SpeciesId> },
+    /// The last organism of a species died or joined another species
+    Extinction,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct SpeciesEvent {
+    /// Simulated seconds since the start
+    pub time: f64,
+    pub species: SpeciesId,
+    pub kind: SpeciesEventKind,
+}
+
+/// Every speciation and extinction so far, in order
+#[derive(Resource, Default)]
+pub struct SpeciesLog {
+    pub events: Vec<SpeciesEvent>,
+}
+
+impl SpeciesLog {
+    fn write_csv(&self, out: &mut impl Write) -> io::Result<()> {
+        writeln!(out, "time,event,species,parent")?;
+        for event in &self.events {
+            match event.kind {
+                SpeciesEventKind::Speciation { parent } => writeln!(
+                    out,
+                    "{},speciation,{},{}",
+                    event.time,
+                    event.species.0,
+                    parent.map_or(String::new(), |parent| parent.0.to_string())
+                )?,
+                SpeciesEventKind::Extinction => {
+                    writeln!(out, "{},extinction,{},", event.time, event.species.0)?
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Sorts every organism into a species and colors it after its species
+fn cluster_species(
+    mut commands: Commands,
+    organisms: Query<(Entity, &Genome, Option<&SpeciesId>, &Handle<ColorMaterial>), With<Organism>>,
+    mut registry: ResMut<SpeciesRegistry>,
+    mut log: ResMut<SpeciesLog>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    clock: Res<SimClock>,
+) {
+    let organisms: Vec<_> = organisms.iter().collect();
+    let assigned = registry.cluster(
+        organisms
+            .iter()
+            .map(|&(_, genome, previous, _)| (genome, previous.copied())),
+        clock.elapsed(),
+        &mut log,
+    );
+
+    for ((entity, _, previous, material), id) in organisms.into_iter().zip(assigned) {
+        if previous == Some(&id) {
+            continue;
+        }
+
+        commands.entity(entity).insert(id);
+        if let Some(material) = materials.get_mut(material) {
+            material.color = registry.species[&id].color;
+        }
+    }
+}
+
+fn export_log(mut exits: EventReader<AppExit>, log: Res<SpeciesLog>, config: Res<SpeciesConfig>) {
+    if exits.read().next().is_none() {
+        return;
+    }
+
+    export::write_file(&config.log_path, "species log", |out| log.write_csv(out));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::neat::NeuralNetwork;
+
+    /// Genomes that are incompatible with each other
+    fn unrelated_genomes() -> [Genome; 3] {
+        let herbivore = Genome::random(TrophicLevel::Herbivore);
+        let carnivore = Genome::random(TrophicLevel::Carnivore);
+        let thinker = Genome {
+            brain: Some(NeuralNetwork::random()),
+            ..herbivore.clone()
+        };
+
+        [herbivore, carnivore, thinker]
+    }
+
+    fn csv(log: &SpeciesLog) -> String {
+        let mut out = Vec::new();
+        log.write_csv(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn organisms_stay_in_their_species_while_compatible() {
+        let genome = Genome::random(TrophicLevel::Herbivore);
+        let relative = Genome {
+            size: genome.size + 0.1,
+            ..genome.clone()
+        };
+        let [_, stranger, _] = unrelated_genomes();
+        let mut registry = SpeciesRegistry::default();
+        let first = registry.found(&genome);
+        let second = registry.found(&relative);
+
+        assert_eq!((first, second), (SpeciesId(0), SpeciesId(1)));
+        assert_eq!(registry.classify(&relative, Some(second)), Some(second));
+        assert_eq!(registry.classify(&relative, None), Some(first));
+        assert_eq!(registry.classify(&stranger, Some(first)), None);
+        assert_eq!(
+            registry.species[&second].color,
+            species_color(second, TrophicLevel::Herbivore)
+        );
+    }
+
+    #[test]
+    fn founders_have_no_parent_species() {
+        let genomes = unrelated_genomes();
+        let mut registry = SpeciesRegistry::default();
+        let mut log = SpeciesLog::default();
+
+        let assigned = registry.cluster(genomes.iter().map(|genome| (genome, None)), 0.0, &mut log);
+
+        assert_eq!(assigned, [SpeciesId(0), SpeciesId(1), SpeciesId(2)]);
+        assert_eq!(
+            csv(&log),
+            "time,event,species,parent\n\
+             0,speciation,0,\n\
+             0,speciation,1,\n\
+             0,speciation,2,\n"
+        );
+    }
+
+    #[test]
+    fn logs_splits_from_the_previous_species_and_extinctions() {
+        let [herbivore, carnivore, _] = unrelated_genomes();
+        let mut registry = SpeciesRegistry::default();
+        let mut log = SpeciesLog::default();
+        registry.cluster([(&herbivore, None), (&carnivore, None)], 0.0, &mut log);
+
+        // The carnivore's offspring turned herbivore, and the carnivores died out
+        let convert = Genome {
+            trophic_level: TrophicLevel::Herbivore,
+            size: herbivore.size + 10.0,
+            ..herbivore.clone()
+        };
+        let assigned = registry.cluster(
+            [
+                (&herbivore, Some(SpeciesId(0))),
+                (&convert, Some(SpeciesId(1))),
+            ],
+            5.0,
+            &mut log,
+        );
+
+        assert_eq!(assigned, [SpeciesId(0), SpeciesId(2)]);
+        assert_eq!(
+            csv(&log),
+            "time,event,species,parent\n\
+             0,speciation,0,\n\
+             0,speciation,1,\n\
+             5,speciation,2,1\n\
+             5,extinction,1,\n"
+        );
+        assert_eq!(
+            registry.species.keys().copied().collect::<Vec<_>>(),
+            [SpeciesId(0), SpeciesId(2)]
+        );
+    }
+}
@@ -0,0 +1,185 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::brain::Brain;
+use crate::genome::Genome;
+use crate::organism::{self, Organism, OrganismSet, Velocity};
+use crate::spatial::SpatialIndex;
+
+pub struct SteeringPlugin {
+    /// Number of nearest organisms considered for separation, alignment and cohesion
+    pub flock_size: usize,
+    /// Only organisms closer than this are part of an organism's flock
+    pub flock_radius: f32,
+    /// Distance from its target at which an arriving organism starts slowing down
+    pub slowing_radius: f32,
+    /// Maximum random change of the wander direction, in radians per second
+    pub wander_jitter: f32,
+}
+
+impl Plugin for SteeringPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(SteeringConfig {
+            flock_size: self.flock_size,
+            flock_radius: self.flock_radius,
+            slowing_radius: self.slowing_radius,
+            wander_jitter: self.wander_jitter,
+        })
+        .add_systems(FixedUpdate, (wander, flock).in_set(OrganismSet::Steer))
+        .add_systems(
+            FixedUpdate,
+            integrate_steering
+                .in_set(OrganismSet::Move)
+                .before(organism::apply_velocity),
+        );
+    }
+}
+
+#[derive(Resource)]
+struct SteeringConfig {
+    flock_size: usize,
+    flock_radius: f32,
+    slowing_radius: f32,
+    wander_jitter: f32,
+}
+
+/// Sum of the steering forces acting on an organism this tick. Steering behaviors
+/// add to it during `OrganismSet::Steer`, and it is turned into a change of
+/// `Velocity` and cleared at the start of `OrganismSet::Move`.
+#[derive(Component, Default, Deref, DerefMut)]
+pub struct SteeringForce(pub Vec2);
+
+/// Heading an organism wanders towards when nothing else draws its attention
+#[derive(Component)]
+pub struct Wander(pub f32);
+
+impl Wander {
+    pub fn random() -> Self {
+        Wander(rand::random::<f32>() * std::f32::consts::TAU)
+    }
+}
+
+/// Force turning `velocity` into moving at full speed in `direction`
+pub fn steer_towards(velocity: Vec2, direction: Vec2, max_speed: f32) -> Vec2 {
+    direction.normalize_or_zero() * max_speed - velocity
+}
+
+/// Force heading straight for `target` at full speed
+pub fn seek(position: Vec2, velocity: Vec2, target: Vec2, max_speed: f32) -> Vec2 {
+    steer_towards(velocity, target - position, max_speed)
+}
+
+/// Force heading straight away from `threat` at full speed
+pub fn flee(position: Vec2, velocity: Vec2, threat: Vec2, max_speed: f32) -> Vec2 {
+    steer_towards(velocity, position - threat, max_speed)
+}
+
+/// Like `seek`, but slowing down within `slowing_radius` of the target to stop on it
+pub fn arrive(
+    position: Vec2,
+    velocity: Vec2,
+    target: Vec2,
+    max_speed: f32,
+    slowing_radius: f32,
+) -> Vec2 {
+    let offset = target - position;
+    let speed = max_speed * (offset.length() / slowing_radius).min(1.0);
+
+    steer_towards(velocity, offset, speed)
+}
+
+/// Slowly and randomly changes every organism's wander heading, and steers
+/// towards it
+fn wander(
+    mut organisms: Query<
+        (&Genome, &Velocity, &mut Wander, &mut SteeringForce),
+        (With<Organism>, Without<Brain>),
+    >,
+    config: Res<SteeringConfig>,
+    time: Res<Time>,
+) {
+    let mut rng = rand::thread_rng();
+    let max_jitter = config.wander_jitter * time.delta_seconds();
+
+    for (genome, velocity, mut wander, mut force) in organisms.iter_mut() {
+        wander.0 += rng.gen_range(-max_jitter..=max_jitter);
+
+        let heading = Vec2::from_angle(wander.0);
+        force.0 += steer_towards(velocity.0, heading, genome.max_speed) * genome.steering.wander;
+    }
+}
+
+/// Separation from, alignment with and cohesion to the nearest organisms of the
+/// same trophic level
+fn flock(
+    mut organisms: Query<
+        (Entity, &Transform, &Genome, &Velocity, &mut SteeringForce),
+        (With<Organism>, Without<Brain>),
+    >,
+    others: Query<(&Genome, &Velocity), With<Organism>>,
+    index: Res<SpatialIndex>,
+    config: Res<SteeringConfig>,
+) {
+    for (entity, transform, genome, velocity, mut force) in organisms.iter_mut() {
+        let position = transform.translation.truncate();
+
+        let mut count = 0;
+        let mut away = Vec2::ZERO;
+        let mut velocity_sum = Vec2::ZERO;
+        let mut position_sum = Vec2::ZERO;
+
+        // One more than the flock size, since the nearest organism is this one
+        for (other, other_position) in index.k_nearest(position, config.flock_size + 1) {
+            let offset = position - other_position;
+            if other == entity || offset.length() > config.flock_radius {
+                continue;
+            }
+            let Ok((other_genome, other_velocity)) = others.get(other) else {
+                continue;
+            };
+            if other_genome.trophic_level != genome.trophic_level {
+                continue;
+            }
+
+            count += 1;
+            // Push harder away from closer organisms
+            away += offset / offset.length_squared().max(f32::EPSILON);
+            velocity_sum += other_velocity.0;
+            position_sum += other_position;
+        }
+
+        if count == 0 {
+            continue;
+        }
+
+        let weights = genome.steering;
+        let separation = steer_towards(velocity.0, away, genome.max_speed);
+        let alignment = velocity_sum / count as f32 - velocity.0;
+        let cohesion = arrive(
+            position,
+            velocity.0,
+            position_sum / count as f32,
+            genome.max_speed,
+            config.slowing_radius,
+        );
+
+        force.0 += separation * weights.separation
+            + alignment * weights.alignment
+            + cohesion * weights.cohesion;
+    }
+}
+
+/// Accelerates every organism by its steering force, limited to the organism's
+/// strongest force, and keeps it under its top speed
+fn integrate_steering(
+    mut organisms: Query<(&Genome, &mut Velocity, &mut SteeringForce), With<Organism>>,
+    time: Res<Time>,
+) {
+    for (genome, mut velocity, mut force) in organisms.iter_mut() {
+        let acceleration = force.0.clamp_length_max(genome.max_force) / genome.body_mass();
+
+        velocity.0 =
+            (velocity.0 + acceleration * time.delta_seconds()).clamp_length_max(genome.max_speed);
+        force.0 = Vec2::ZERO;
+    }
+}
@@ -0,0 +1,253 @@
+use bevy::prelude::*;
+use noise::{Fbm, MultiFractal, NoiseFn, Perlin};
+
+use crate::boundary::{self, BoundaryMode};
+use crate::field::{Field, GridGeometry, ScalarField};
+use crate::genome::Genome;
+use crate::heat_diffusion::{LapseCooling, TemperatureField};
+use crate::material::{Material, MaterialField};
+use crate::organism::{Energy, Organism, OrganismSet, Velocity};
+use crate::overlay;
+use crate::steering::{self, SteeringForce};
+
+const FREEZING_TEMPERATURE: f32 = 20.0; // Water starting out colder than this is frozen
+const SHORE_HEIGHT: f32 = 0.05; // Fraction of the maximum elevation above sea level covered by sand
+const TREE_LINE: f32 = 0.5; // Fraction of the maximum elevation above which the ground is bare rock
+const CLIMB_COST: f32 = 0.001; // Energy per unit of body mass per unit of elevation climbed
+const RELIEF_OPACITY: f32 = 0.5;
+
+pub struct TerrainPlugin {
+    pub seed: u32,
+    /// Number of noise layers added together, each with finer detail than the last
+    pub octaves: usize,
+    /// Frequency of the coarsest noise layer, in cycles per cell
+    pub frequency: f64,
+    /// Amplitude of each noise layer relative to the previous one
+    pub persistence: f64,
+    /// Highest possible elevation, and depth of the deepest sea
+    pub max_elevation: f32,
+    pub sea_level: f32,
+    /// Degrees of cooling per unit of elevation above sea level
+    pub lapse_rate: f32,
+    /// Organisms steer around slopes steeper than this, in elevation per world unit
+    pub max_slope: f32,
+}
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TerrainConfig {
+            seed: self.seed,
+            octaves: self.octaves,
+            frequency: self.frequency,
+            persistence: self.persistence,
+            max_elevation: self.max_elevation,
+            sea_level: self.sea_level,
+            lapse_rate: self.lapse_rate,
+            max_slope: self.max_slope,
+        })
+        .add_systems(Startup, (generate_terrain, setup_relief).chain())
+        .add_systems(FixedUpdate, avoid_steep_slopes.in_set(OrganismSet::Steer))
+        .add_systems(
+            FixedUpdate,
+            pay_climbing_cost.in_set(OrganismSet::Metabolize),
+        )
+        .add_systems(Update, toggle_relief);
+    }
+}
+
+#[derive(Resource)]
+struct TerrainConfig {
+    seed: u32,
+    octaves: usize,
+    frequency: f64,
+    persistence: f64,
+    max_elevation: f32,
+    sea_level: f32,
+    lapse_rate: f32,
+    max_slope: f32,
+}
+
+impl TerrainConfig {
+    fn generate_elevation(&self, geometry: GridGeometry) -> ScalarField {
+        let fbm = Fbm::<Perlin>::new(self.seed)
+            .set_octaves(self.octaves)
+            .set_frequency(self.frequency)
+            .set_persistence(self.persistence);
+
+        ScalarField::from_fn(geometry, |x, y| {
+            fbm.get([x as f64, y as f64]).clamp(-1.0, 1.0) as f32 * self.max_elevation
+        })
+    }
+
+    /// Water below sea level, freezing where it starts out cold, then sand along
+    /// the shores, soil on the lowlands and bare rock high up.
+    fn lay_out_materials(&self, terrain: &Terrain, temperatures: &ScalarField) -> Field<Material> {
+        Field::from_fn(*temperatures.geometry(), |x, y| {
+            let height = (terrain.elevation.get(x, y) - self.sea_level) / self.max_elevation;
+
+            match height {
+                h if h < 0.0 && temperatures.get(x, y) < FREEZING_TEMPERATURE => Material::Ice,
+                h if h < 0.0 => Material::Water,
+                h if h < SHORE_HEIGHT => Material::Sand,
+                h if h < TREE_LINE => Material::Soil,
+                _ => Material::Rock,
+            }
+        })
+    }
+}
+
+/// Raises the terrain over the grid and lays out the materials on it
+fn generate_terrain(
+    mut commands: Commands,
+    mut temperatures: ResMut<TemperatureField>,
+    geometry: Res<GridGeometry>,
+    config: Res<TerrainConfig>,
+) {
+    let terrain = Terrain {
+        elevation: config.generate_elevation(*geometry),
+        sea_level: config.sea_level,
+        lapse_rate: config.lapse_rate,
+    };
+
+    // Start mountains out as cold as they will be kept by the lapse rate
+    for y in 0..geometry.height {
+        for x in 0..geometry.width {
+            *temperatures.get_mut(x, y) -= terrain.lapse_cooling(x, y);
+        }
+    }
+
+    commands.insert_resource(MaterialField(
+        config.lay_out_materials(&terrain, &temperatures),
+    ));
+    commands.insert_resource(LapseCooling(ScalarField::from_fn(*geometry, |x, y| {
+        terrain.lapse_cooling(x, y)
+    })));
+    commands.insert_resource(terrain);
+}
+
+/// Elevation of every grid cell, and how it affects the climate
+#[derive(Resource)]
+pub struct Terrain {
+    elevation: ScalarField,
+    sea_level: f32,
+    lapse_rate: f32,
+}
+
+impl Terrain {
+    /// How much colder a cell is than it would be at sea level. Cells below
+    /// sea level are under water, so they are not warmed by their depth.
+    pub fn lapse_cooling(&self, x: usize, y: usize) -> f32 {
+        (self.elevation.get(x, y) - self.sea_level).max(0.0) * self.lapse_rate
+    }
+}
+
+#[derive(Component)]
+struct Relief;
+
+/// Steers organisms heading towards a slope that is too steep to follow the
+/// contour line instead. Slopes are looked for one cell ahead.
+fn avoid_steep_slopes(
+    mut organisms: Query<(&Transform, &Genome, &Velocity, &mut SteeringForce), With<Organism>>,
+    terrain: Res<Terrain>,
+    geometry: Res<GridGeometry>,
+    config: Res<TerrainConfig>,
+    mode: Option<Res<BoundaryMode>>,
+) {
+    let wrap = boundary::world_wrap(mode.as_deref());
+
+    for (transform, genome, velocity, mut force) in organisms.iter_mut() {
+        let Some(direction) = velocity.0.try_normalize() else {
+            continue;
+        };
+        let ahead = transform.translation.truncate() + direction * geometry.cell_size;
+        let gradient = terrain.elevation.gradient(ahead, geometry.cell_size, wrap);
+
+        if gradient.dot(direction) <= config.max_slope {
+            continue;
+        }
+
+        // Of the two directions along the contour, keep the one closest to the current heading
+        let contour = gradient.perp();
+        let contour = if contour.dot(direction) < 0.0 {
+            -contour
+        } else {
+            contour
+        };
+        force.0 += steering::steer_towards(velocity.0, contour, genome.max_speed)
+            * genome.steering.obstacle_avoidance;
+    }
+}
+
+/// Climbing takes energy in proportion to body mass and elevation gained
+fn pay_climbing_cost(
+    mut organisms: Query<(&Transform, &Genome, &Velocity, &mut Energy), With<Organism>>,
+    terrain: Res<Terrain>,
+    geometry: Res<GridGeometry>,
+    time: Res<Time>,
+    mode: Option<Res<BoundaryMode>>,
+) {
+    let wrap = boundary::world_wrap(mode.as_deref());
+
+    for (transform, genome, velocity, mut energy) in organisms.iter_mut() {
+        let position = transform.translation.truncate();
+        let gradient = terrain
+            .elevation
+            .gradient(position, geometry.cell_size, wrap);
+        let climb_rate = gradient.dot(velocity.0).max(0.0);
+
+        energy.0 -= CLIMB_COST * genome.body_mass() * climb_rate * time.delta_seconds();
+    }
+}
+
+/// Shading of each cell lit by a sun in the north west, 1 on slopes facing it
+/// and 0 on slopes facing away
+fn hillshade(terrain: &Terrain, geometry: &GridGeometry, wrap: BVec2) -> ScalarField {
+    let light = Vec3::new(-1.0, 1.0, 1.0).normalize();
+
+    ScalarField::from_fn(*geometry, |x, y| {
+        let gradient =
+            terrain
+                .elevation
+                .gradient(geometry.cell_center(x, y), geometry.cell_size, wrap);
+        let normal = Vec3::new(-gradient.x, -gradient.y, 1.0).normalize();
+
+        normal.dot(light).max(0.0)
+    })
+}
+
+/// The terrain never changes, so its relief is drawn once on a layer above the overlay
+fn setup_relief(
+    mut commands: Commands,
+    mut images: ResMut<Assets<Image>>,
+    terrain: Res<Terrain>,
+    geometry: Res<GridGeometry>,
+    mode: Option<Res<BoundaryMode>>,
+) {
+    let wrap = boundary::world_wrap(mode.as_deref());
+    let mut image = overlay::grid_image(&geometry);
+    overlay::paint_image(&mut image, &hillshade(&terrain, &geometry, wrap), |shade| {
+        Color::srgba(shade, shade, shade, RELIEF_OPACITY)
+    });
+
+    commands.spawn((
+        Relief,
+        overlay::grid_sprite(images.add(image), &geometry, 0.5),
+    ));
+}
+
+/// Shows or hides the relief over whichever overlay is active
+fn toggle_relief(
+    mut relief: Query<&mut Visibility, With<Relief>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+) {
+    if !keyboard_input.just_pressed(KeyCode::KeyR) {
+        return;
+    }
+
+    for mut visibility in relief.iter_mut() {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Inherited,
+            _ => Visibility::Hidden,
+        };
+    }
+}
@@ -0,0 +1,75 @@
+use bevy::prelude::*;
+
+use crate::boundary::{self, BoundaryMode};
+use crate::brain::Brain;
+use crate::genome::Genome;
+use crate::heat_diffusion::TemperatureField;
+use crate::organism::{Organism, OrganismSet, Velocity};
+use crate::steering::{self, SteeringForce};
+
+pub struct ThermotaxisPlugin {
+    /// Distance from the organism at which the temperature gradient is sampled
+    pub sensing_radius: f32,
+    /// Weight of thermotaxis against the organism's other steering behaviors
+    pub strength: f32,
+    /// Fastest an organism is steered to turn towards a better temperature, in radians per second
+    pub turn_rate: f32,
+}
+
+impl Plugin for ThermotaxisPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(ThermotaxisConfig {
+            sensing_radius: self.sensing_radius,
+            strength: self.strength,
+            turn_rate: self.turn_rate,
+        })
+        .add_systems(FixedUpdate, thermotaxis.in_set(OrganismSet::Steer));
+    }
+}
+
+#[derive(Resource)]
+struct ThermotaxisConfig {
+    sensing_radius: f32,
+    strength: f32,
+    turn_rate: f32,
+}
+
+/// Steers each organism up or down the local temperature gradient, depending on
+/// whether it is colder or warmer than it would like to be. Organisms are only
+/// steered to turn by up to the configured turn rate at a time.
+fn thermotaxis(
+    mut organisms: Query<
+        (&Transform, &Genome, &Velocity, &mut SteeringForce),
+        (With<Organism>, Without<Brain>),
+    >,
+    temperature: Res<TemperatureField>,
+    config: Res<ThermotaxisConfig>,
+    mode: Option<Res<BoundaryMode>>,
+    time: Res<Time>,
+) {
+    let wrap = boundary::world_wrap(mode.as_deref());
+    let max_turn = config.turn_rate * time.delta_seconds();
+
+    for (transform, genome, velocity, mut force) in organisms.iter_mut() {
+        let position = transform.translation.truncate();
+        let Some(current) = temperature.sample(position) else {
+            continue;
+        };
+
+        let gradient = temperature.gradient(position, config.sensing_radius, wrap);
+
+        // Climb the gradient when too cold, descend it when too warm
+        let mut desired = gradient * (genome.preferred_temperature - current).signum();
+        if desired == Vec2::ZERO {
+            continue;
+        }
+
+        // Aim no further from the current heading than the turn rate allows
+        if velocity.0 != Vec2::ZERO {
+            let angle = velocity.0.angle_between(desired);
+            desired = Vec2::from_angle(angle.clamp(-max_turn, max_turn)).rotate(velocity.0);
+        }
+
+        force.0 += steering::steer_towards(velocity.0, desired, genome.max_speed) * config.strength;
+    }
+}
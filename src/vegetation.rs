@@ -0,0 +1,105 @@
+use bevy::prelude::*;
+
+use crate::brain::{self, Brain};
+use crate::field::{GridGeometry, ScalarField};
+use crate::genome::{Genome, TrophicLevel};
+use crate::heat_diffusion::TemperatureField;
+use crate::organism::{Energy, Organism, OrganismSet};
+use crate::overlay::{self, Overlay, OverlayImage};
+
+const INITIAL_BIOMASS: f32 = 50.0;
+pub const MAXIMUM_BIOMASS: f32 = 100.0;
+const GROWTH_RATE: f32 = 0.02; // Logistic growth rate per second at the optimal temperature
+const OPTIMAL_GROWTH_TEMPERATURE: f32 = 55.0;
+const GROWTH_TEMPERATURE_TOLERANCE: f32 = 15.0; // Width of the thermal optimum curve
+const GRAZING_RATE: f32 = 5.0; // Biomass eaten per second for a body mass of 1
+const GRAZING_EFFICIENCY: f32 = 0.8; // Fraction of eaten biomass converted into energy
+
+pub struct VegetationPlugin;
+
+impl Plugin for VegetationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, setup)
+            .add_systems(
+                FixedUpdate,
+                (grow_vegetation, graze)
+                    .chain()
+                    .in_set(OrganismSet::Metabolize),
+            )
+            .add_systems(
+                Update,
+                visualize_vegetation.run_if(resource_equals(Overlay::Vegetation)),
+            );
+    }
+}
+
+/// Amount of plant food available on every grid cell
+#[derive(Resource, Deref, DerefMut)]
+pub struct BiomassField(pub ScalarField);
+
+fn setup(mut commands: Commands, geometry: Res<GridGeometry>) {
+    commands.insert_resource(BiomassField(ScalarField::new(*geometry, INITIAL_BIOMASS)));
+}
+
+/// Relative growth rate in [0, 1] following a bell curve around the optimal temperature
+fn thermal_growth_factor(temperature: f32) -> f32 {
+    let deviation = (temperature - OPTIMAL_GROWTH_TEMPERATURE) / GROWTH_TEMPERATURE_TOLERANCE;
+
+    (-0.5 * deviation.powi(2)).exp()
+}
+
+fn grow_vegetation(
+    mut biomass: ResMut<BiomassField>,
+    temperature: Res<TemperatureField>,
+    time: Res<Time>,
+) {
+    for (biomass, &temperature) in biomass.values_mut().iter_mut().zip(temperature.values()) {
+        let growth = GROWTH_RATE
+            * thermal_growth_factor(temperature)
+            * *biomass
+            * (1.0 - *biomass / MAXIMUM_BIOMASS);
+
+        *biomass = (*biomass + growth * time.delta_seconds()).clamp(0.0, MAXIMUM_BIOMASS);
+    }
+}
+
+/// Converts the biomass of the cell under each organism into energy
+fn graze(
+    mut organisms: Query<(&Transform, &Genome, &mut Energy, Option<&Brain>), With<Organism>>,
+    mut biomass: ResMut<BiomassField>,
+    time: Res<Time>,
+) {
+    for (transform, genome, mut energy, brain) in organisms.iter_mut() {
+        if genome.trophic_level != TrophicLevel::Herbivore || !brain::wants_to_eat(brain) {
+            continue;
+        }
+
+        let Some((x, y)) = biomass
+            .geometry()
+            .world_to_cell(transform.translation.truncate())
+        else {
+            continue;
+        };
+        let available = biomass.get_mut(x, y);
+
+        let eaten = (GRAZING_RATE * genome.body_mass() * time.delta_seconds()).min(*available);
+        *available -= eaten;
+        energy.0 += eaten * GRAZING_EFFICIENCY;
+    }
+}
+
+fn visualize_vegetation(
+    biomass: Res<BiomassField>,
+    overlay_image: Res<OverlayImage>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    overlay::paint(&mut images, &overlay_image, &biomass, |biomass| {
+        let biomass_ratio = biomass / MAXIMUM_BIOMASS;
+
+        Color::srgb(
+            0.55 - 0.45 * biomass_ratio,
+            0.45 + 0.2 * biomass_ratio,
+            0.25 - 0.15 * biomass_ratio,
+        )
+    });
+}
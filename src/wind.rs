@@ -0,0 +1,131 @@
+use bevy::{color::palettes::css, prelude::*};
+use noise::{NoiseFn, Perlin};
+
+use crate::field::{Field, GridGeometry};
+use crate::organism::{Organism, OrganismSet};
+use crate::steering::SteeringForce;
+
+const ARROW_SPACING: usize = 4; // Cells between the wind arrows drawn along each axis
+const ARROW_SCALE: f32 = 1.0; // Length of a wind arrow per unit of wind speed
+
+pub struct WindPlugin {
+    pub seed: u32,
+    /// Wind blowing uniformly over the whole grid, in world units per second
+    pub prevailing: Vec2,
+    /// Typical speed of the eddies on top of the prevailing wind
+    pub turbulence: f32,
+    /// Frequency of the eddies, in cycles per cell
+    pub frequency: f64,
+    /// Force with which the wind pushes an organism per unit of wind speed
+    pub drag: f32,
+}
+
+impl Plugin for WindPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(WindConfig {
+            seed: self.seed,
+            prevailing: self.prevailing,
+            turbulence: self.turbulence,
+            frequency: self.frequency,
+            drag: self.drag,
+        })
+        .insert_resource(WindArrows(false))
+        .add_systems(Startup, generate_wind)
+        .add_systems(FixedUpdate, drift_with_wind.in_set(OrganismSet::Steer))
+        .add_systems(
+            Update,
+            (
+                toggle_wind_arrows,
+                draw_wind_arrows.run_if(resource_equals(WindArrows(true))),
+            )
+                .chain(),
+        );
+    }
+}
+
+/// Wind velocity of every grid cell, in world units per second
+#[derive(Resource, Deref, DerefMut)]
+pub struct WindField(pub Field<Vec2>);
+
+#[derive(Resource)]
+struct WindConfig {
+    seed: u32,
+    prevailing: Vec2,
+    turbulence: f32,
+    frequency: f64,
+    drag: f32,
+}
+
+impl WindConfig {
+    /// Eddies are the curl of a noise field, which makes them swirl without
+    /// piling air up anywhere or leaving it empty.
+    fn generate_wind(&self, geometry: GridGeometry) -> Field<Vec2> {
+        let perlin = Perlin::new(self.seed);
+        let stream = |x: f64, y: f64| perlin.get([x * self.frequency, y * self.frequency]) as f32;
+
+        Field::from_fn(geometry, |x, y| {
+            let (x, y) = (x as f64, y as f64);
+            let eddy = Vec2::new(
+                stream(x, y + 0.5) - stream(x, y - 0.5),
+                stream(x - 0.5, y) - stream(x + 0.5, y),
+            ) / self.frequency as f32;
+
+            self.prevailing + eddy * self.turbulence
+        })
+    }
+}
+
+/// Wind generated with the settings of the simulation, blowing over the given grid
+#[cfg(test)]
+pub fn test_wind(geometry: GridGeometry) -> Field<Vec2> {
+    let config = WindConfig {
+        seed: 7,
+        prevailing: Vec2::new(8.0, 0.0),
+        turbulence: 20.0,
+        frequency: 0.08,
+        drag: 0.2,
+    };
+
+    config.generate_wind(geometry)
+}
+
+fn generate_wind(mut commands: Commands, geometry: Res<GridGeometry>, config: Res<WindConfig>) {
+    commands.insert_resource(WindField(config.generate_wind(*geometry)));
+}
+
+/// Whether arrows showing the wind are drawn over the grid
+#[derive(Resource, Debug, PartialEq, Eq, Clone, Copy)]
+struct WindArrows(bool);
+
+/// Pushes organisms along with the wind. Lighter organisms are accelerated more,
+/// and all of them have to push back against it to move upwind.
+fn drift_with_wind(
+    mut organisms: Query<(&Transform, &mut SteeringForce), With<Organism>>,
+    wind: Res<WindField>,
+    config: Res<WindConfig>,
+) {
+    for (transform, mut force) in organisms.iter_mut() {
+        if let Some(wind) = wind.sample(transform.translation.truncate()) {
+            force.0 += wind * config.drag;
+        }
+    }
+}
+
+fn toggle_wind_arrows(mut arrows: ResMut<WindArrows>, keyboard_input: Res<ButtonInput<KeyCode>>) {
+    if keyboard_input.just_pressed(KeyCode::KeyW) {
+        arrows.0 = !arrows.0;
+    }
+}
+
+fn draw_wind_arrows(mut gizmos: Gizmos, wind: Res<WindField>) {
+    let geometry = wind.geometry();
+
+    for y in (0..geometry.height).step_by(ARROW_SPACING) {
+        for x in (0..geometry.width).step_by(ARROW_SPACING) {
+            let start = geometry.cell_center(x, y);
+            let end = start + wind.get(x, y) * ARROW_SCALE;
+
+            gizmos.arrow_2d(start, end, css::WHITE);
+        }
+    }
+}